@@ -1,4 +1,6 @@
 use crate::lexer;
+use crate::lexer::TokenKind;
+use crate::types::Type;
 use std::fmt;
 
 #[derive(Clone)]
@@ -16,89 +18,700 @@ pub enum Node {
     CompoundExpr {
         nodes: Vec<Node>,
     },
+    Program {
+        nodes: Vec<Node>,
+    },
+    // `params` are `Variable`s, and `frame_size` is how much stack the body needs, both
+    // worked out by the semantic pass
+    FnDecl {
+        token: lexer::Token,
+        name: lexer::Token,
+        params: Vec<Node>,
+        ret_ty: Type,
+        body: Box<Node>,
+        frame_size: i64,
+    },
+    // `ty` is the return type of the function being called, filled in by the semantic pass.
+    // A struct or array that is returned is kept in a hidden slot at `offset`.
+    Call {
+        token: lexer::Token,
+        args: Vec<Node>,
+        ty: Type,
+        offset: i64,
+    },
+    Cast {
+        token: lexer::Token,
+        node: Box<Node>,
+        ty: Type,
+    },
+    // `otherwise` is either the `else` block or another `IfStmt` for `else if`
+    IfStmt {
+        token: lexer::Token,
+        condition: Box<Node>,
+        then: Box<Node>,
+        otherwise: Option<Box<Node>>,
+    },
+    // `offset` is the variable's slot below %rbp and `ty` its type, both filled in by the
+    // semantic pass, which also sets `global` if the name is a top level `const` or `static`
+    Variable {
+        token: lexer::Token,
+        offset: i64,
+        global: bool,
+        ty: Type,
+    },
+    // `ty` is the annotation if there was one, otherwise the semantic pass sets it to the type
+    // of `value`. `token` is either `let` or `const`, and only `let mut` is `mutable`.
+    LetDecl {
+        token: lexer::Token,
+        name: lexer::Token,
+        mutable: bool,
+        ty: Option<Type>,
+        value: Box<Node>,
+        offset: i64,
+    },
+    // `loop` is a `while` without a condition
+    WhileStmt {
+        token: lexer::Token,
+        label: Option<lexer::Token>,
+        condition: Option<Box<Node>>,
+        body: Box<Node>,
+    },
+    // `end_offset` is a hidden slot that holds the upper bound, so it is only evaluated once
+    ForStmt {
+        token: lexer::Token,
+        label: Option<lexer::Token>,
+        variable: Box<Node>,
+        start: Box<Node>,
+        end: Box<Node>,
+        body: Box<Node>,
+        end_offset: i64,
+    },
+    // `for x in iterable`, over the elements of an array or vec. What is looped over is kept
+    // in a hidden slot at `offset`, as its address if it is an array, and the index of the
+    // next element in the slot after it.
+    ForEach {
+        token: lexer::Token,
+        label: Option<lexer::Token>,
+        variable: Box<Node>,
+        iterable: Box<Node>,
+        body: Box<Node>,
+        offset: i64,
+    },
+    // `break` and `continue`, told apart by their token
+    LoopControl {
+        token: lexer::Token,
+        label: Option<lexer::Token>,
+    },
+    // `[a, b, c]`, built in a hidden slot at `offset`. `ty` is worked out by the semantic pass
+    // from the first element.
+    ArrayLiteral {
+        token: lexer::Token,
+        elements: Vec<Node>,
+        ty: Type,
+        offset: i64,
+    },
+    // `array[index]`, where `ty` is the element type, filled in by the semantic pass
+    Index {
+        token: lexer::Token,
+        array: Box<Node>,
+        index: Box<Node>,
+        ty: Type,
+    },
+    // `struct Name { field: type, ... }`, whose layout the semantic pass works out
+    StructDecl {
+        token: lexer::Token,
+        name: lexer::Token,
+        fields: Vec<(lexer::Token, Type)>,
+    },
+    // `Name { field: value, ... }`, with the fields in the order they were written, built in a
+    // hidden slot at `offset` like an array literal
+    StructLiteral {
+        token: lexer::Token,
+        fields: Vec<(lexer::Token, Node)>,
+        ty: Type,
+        offset: i64,
+    },
+    // `node.name`, where `ty` is the type of the field, filled in by the semantic pass
+    Field {
+        token: lexer::Token,
+        node: Box<Node>,
+        name: lexer::Token,
+        ty: Type,
+    },
+    // `type Name = type;` or `newtype Name = type;`, depending on `token`
+    TypeDecl {
+        token: lexer::Token,
+        name: lexer::Token,
+        ty: Type,
+    },
+    // `enum Name { Variant(type, ...), ... }`, where a variant can leave out the brackets if it
+    // has no fields
+    EnumDecl {
+        token: lexer::Token,
+        name: lexer::Token,
+        variants: Vec<(lexer::Token, Vec<Type>)>,
+    },
+    // `Name::Variant(value, ...)`, where `token` is the name of the enum. It is built in a
+    // hidden slot at `offset` like a struct literal.
+    Variant {
+        token: lexer::Token,
+        name: lexer::Token,
+        args: Vec<Node>,
+        ty: Type,
+        offset: i64,
+    },
+    // `match node { pattern => value, ... }`, which is the value of the first arm whose pattern
+    // matches. What is matched on is kept in a hidden slot at `offset` while the patterns are
+    // tried, as its address if it is an aggregate. `ty` is worked out by the type checker.
+    Match {
+        token: lexer::Token,
+        node: Box<Node>,
+        arms: Vec<(Pattern, Node)>,
+        ty: Type,
+        offset: i64,
+    },
+    // a top level `const`, `static` or `static mut`, told apart by `token` and `mutable`. The
+    // type checker works out `value` and replaces it with the literal it comes to.
+    GlobalDecl {
+        token: lexer::Token,
+        name: lexer::Token,
+        mutable: bool,
+        ty: Type,
+        value: Box<Node>,
+    },
+}
+
+impl Node {
+    // The type of the value an expression leaves behind, worked out from the types the
+    // semantic pass filled in. A literal without a suffix takes on the type of whatever it is
+    // combined with, so `x + 1` has the type of `x`.
+    pub fn ty(&self) -> Type {
+        match self {
+            Node::Primary(lexer::Token {
+                kind: TokenKind::INT(x),
+                ..
+            }) => x.ty(),
+            Node::Primary(lexer::Token {
+                kind: TokenKind::FLOAT(..),
+                ..
+            }) => Type::F64,
+            Node::Primary(lexer::Token {
+                kind: TokenKind::STR(..),
+                ..
+            }) => Type::Str,
+            Node::Primary(lexer::Token {
+                kind: TokenKind::CHAR(..),
+                ..
+            }) => Type::Char,
+            Node::Primary(lexer::Token {
+                kind: TokenKind::KEYWORD(lexer::KeywordKind::TRUE | lexer::KeywordKind::FALSE),
+                ..
+            }) => Type::Bool,
+            // unless the type checker finds it a pointer type, `null` points at nothing
+            Node::Primary(lexer::Token {
+                kind: TokenKind::KEYWORD(lexer::KeywordKind::NULL),
+                ..
+            }) => Type::Pointer(Box::new(Type::Unit)),
+            Node::Variable { ty, .. }
+            | Node::Call { ty, .. }
+            | Node::Cast { ty, .. }
+            | Node::ArrayLiteral { ty, .. }
+            | Node::Index { ty, .. }
+            | Node::StructLiteral { ty, .. }
+            | Node::Field { ty, .. }
+            | Node::Variant { ty, .. }
+            | Node::Match { ty, .. } => ty.clone(),
+            Node::UnaryExpr { token, node } => match token.kind {
+                TokenKind::PLUS | TokenKind::MINUS | TokenKind::BIT_NOT => node.ty(),
+                TokenKind::NOT => Type::Bool,
+                TokenKind::MUL => match node.ty().repr() {
+                    Type::Pointer(ty) => *ty,
+                    _ => Type::Unit,
+                },
+                TokenKind::BIT_AND => Type::Pointer(Box::new(node.ty())),
+                _ => Type::Unit,
+            },
+            Node::BinaryExpr { token, left, right } => match token.kind {
+                TokenKind::EQUALS => left.ty(),
+                TokenKind::SHIFT_LEFT
+                | TokenKind::SHIFT_RIGHT
+                | TokenKind::UNSIGNED_SHIFT_RIGHT => left.ty(),
+                TokenKind::DOUBLE_EQUALS
+                | TokenKind::NOT_EQUALS
+                | TokenKind::LESS_THAN
+                | TokenKind::LESS_EQUALS
+                | TokenKind::GREATER_THAN
+                | TokenKind::GREATER_EQUALS
+                | TokenKind::AND
+                | TokenKind::OR => Type::Bool,
+                // two pointers are however many elements apart
+                TokenKind::MINUS if left.ty().is_pointer() && right.ty().is_pointer() => Type::I64,
+                _ => Node::operand_type(left, right),
+            },
+            _ => Type::Unit,
+        }
+    }
+
+    // the token an error about this node should point at
+    pub fn token(&self) -> Option<&lexer::Token> {
+        match self {
+            Node::Primary(token)
+            | Node::Variable { token, .. }
+            | Node::Call { token, .. }
+            | Node::UnaryExpr { token, .. }
+            | Node::Cast { token, .. }
+            | Node::LetDecl { token, .. }
+            | Node::FnDecl { token, .. }
+            | Node::IfStmt { token, .. }
+            | Node::WhileStmt { token, .. }
+            | Node::ForStmt { token, .. }
+            | Node::ForEach { token, .. }
+            | Node::LoopControl { token, .. }
+            | Node::ArrayLiteral { token, .. }
+            | Node::StructDecl { token, .. }
+            | Node::StructLiteral { token, .. }
+            | Node::EnumDecl { token, .. }
+            | Node::TypeDecl { token, .. }
+            | Node::Variant { token, .. }
+            | Node::Match { token, .. }
+            | Node::GlobalDecl { token, .. } => Some(token),
+            Node::Index { token, array, .. } => array.token().or(Some(token)),
+            Node::Field { token, node, .. } => node.token().or(Some(token)),
+            // point at the start of the expression rather than the operator
+            Node::BinaryExpr { token, left, .. } => left.token().or(Some(token)),
+            Node::CompoundExpr { .. } | Node::Program { .. } => None,
+        }
+    }
+
+    // the type both sides of a binary operator are worked out in
+    pub fn operand_type(left: &Node, right: &Node) -> Type {
+        if left.is_untyped_literal() {
+            right.ty()
+        } else {
+            left.ty()
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(
+            self,
+            Node::Primary(lexer::Token {
+                kind: TokenKind::KEYWORD(lexer::KeywordKind::NULL),
+                ..
+            })
+        )
+    }
+
+    pub fn is_untyped_literal(&self) -> bool {
+        match self {
+            Node::Primary(lexer::Token {
+                kind: TokenKind::INT(x),
+                ..
+            }) => x.ty.is_none(),
+            Node::UnaryExpr { token, node } => {
+                matches!(
+                    token.kind,
+                    TokenKind::PLUS | TokenKind::MINUS | TokenKind::BIT_NOT
+                ) && node.is_untyped_literal()
+            }
+            Node::BinaryExpr { left, right, .. } => {
+                left.is_untyped_literal() && right.is_untyped_literal()
+            }
+            _ => false,
+        }
+    }
+}
+
+// what a `match` arm compares against
+#[derive(Clone)]
+pub enum Pattern {
+    // `_`, which matches anything
+    Wildcard(lexer::Token),
+    // a name, which matches anything and is declared as a `Variable` holding it
+    Binding(Node),
+    // an integer, char or bool literal, where an integer can have a minus in front
+    Literal(Node),
+    // `start..end` or `start..=end`, both ends being literals
+    Range {
+        token: lexer::Token,
+        start: Box<Node>,
+        end: Box<Node>,
+        inclusive: bool,
+    },
+    // `Name::Variant(pattern, ...)`, which matches that variant if each of its fields matches
+    // the pattern in the same position. `token` is the name of the enum.
+    Variant {
+        token: lexer::Token,
+        name: lexer::Token,
+        fields: Vec<Pattern>,
+    },
+}
+
+impl Pattern {
+    pub fn token(&self) -> &lexer::Token {
+        match self {
+            Pattern::Wildcard(token)
+            | Pattern::Range { token, .. }
+            | Pattern::Variant { token, .. } => token,
+            Pattern::Binding(node) | Pattern::Literal(node) => {
+                node.token().expect("pattern without a token")
+            }
+        }
+    }
+}
+
+// patterns are printed the way they were written
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Wildcard(..) => write!(f, "_"),
+            Pattern::Binding(node) | Pattern::Literal(node) => write!(f, "{}", source_text(node)),
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let op = if *inclusive { "..=" } else { ".." };
+                write!(f, "{}{}{}", source_text(start), op, source_text(end))
+            }
+            Pattern::Variant {
+                token,
+                name,
+                fields,
+            } => {
+                write!(f, "{}::{}", token_text(token), token_text(name))?;
+                if !fields.is_empty() {
+                    let fields: Vec<String> = fields.iter().map(|x| x.to_string()).collect();
+                    write!(f, "({})", fields.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// a name or literal inside a pattern as it would have been written
+fn source_text(node: &Node) -> String {
+    match node {
+        Node::UnaryExpr { node, .. } => format!("-{}", source_text(node)),
+        Node::Primary(token) | Node::Variable { token, .. } => token_text(token),
+        _ => "?".to_string(),
+    }
+}
+
+fn token_text(token: &lexer::Token) -> String {
+    match &token.kind {
+        TokenKind::INT(x) => x.value.to_string(),
+        TokenKind::CHAR(x) => format!("{:?}", x),
+        TokenKind::DECL_NAME(x) => x.clone(),
+        TokenKind::KEYWORD(lexer::KeywordKind::TRUE) => "true".to_string(),
+        TokenKind::KEYWORD(lexer::KeywordKind::FALSE) => "false".to_string(),
+        kind => format!("{:?}", kind),
+    }
 }
 
 // TODO: Better tree printing?
 impl fmt::Display for Node {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Node::print_tree(self.clone(), f)
+        TreePrinter::default().print_tree(self, f)
     }
 }
 
-static mut indentation: usize = 0;
-static mut mask: Vec<bool> = Vec::new();
-impl Node {
-    pub fn print_tree(node: Node, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        unsafe {
-            if mask.len() <= indentation {
-                mask.resize(32, false);
-            }
+fn label_prefix(label: &Option<lexer::Token>) -> String {
+    match label {
+        Some(label) => format!("{} ", label),
+        None => String::new(),
+    }
+}
+
+// `mask[i]` is set while the node at depth `i` still has children left to print, so that a
+// `|` is drawn in that column for everything printed underneath it.
+#[derive(Default)]
+struct TreePrinter {
+    indentation: usize,
+    mask: Vec<bool>,
+}
+
+impl TreePrinter {
+    fn print_tree(&mut self, node: &Node, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.mask.len() <= self.indentation {
+            self.mask.resize(self.indentation + 1, false);
         }
         match node {
-            Node::Primary(x) => {
-                return Node::indented_print(format!("{}", x), f);
-            }
-            Node::UnaryExpr { token, node } => unsafe {
-                Node::indented_print(format!("Unary: {}", token), f)?;
-                indentation += 1;
-                Node::print_tree(*node, f)?;
-                indentation -= 1;
+            Node::Primary(x) => self.indented_print(format!("{}", x), f),
+            Node::Variable { token, .. } => self.indented_print(format!("{}", token), f),
+            Node::UnaryExpr { token, node } => {
+                self.indented_print(format!("Unary: {}", token), f)?;
+                self.indentation += 1;
+                self.print_tree(node, f)?;
+                self.indentation -= 1;
                 Ok(())
-            },
-            Node::BinaryExpr { token, left, right } => unsafe {
-                Node::indented_print(format!("Binary: {}", token), f)?;
-                let binary_indent = indentation;
-                indentation += 1;
+            }
+            Node::BinaryExpr { token, left, right } => {
+                self.indented_print(format!("Binary: {}", token), f)?;
+                let binary_indent = self.indentation;
+                self.indentation += 1;
 
-                mask[binary_indent] = true;
-                Node::print_tree(*left, f)?;
-                mask[binary_indent] = false;
-                Node::print_tree(*right, f)?;
+                self.mask[binary_indent] = true;
+                self.print_tree(left, f)?;
+                self.mask[binary_indent] = false;
+                self.print_tree(right, f)?;
 
-                indentation -= 1;
+                self.indentation -= 1;
                 Ok(())
-            },
-            Node::CompoundExpr { nodes } => unsafe {
-                Node::indented_print("Compound:".to_string(), f)?;
-
-                let compound_indent = indentation;
-                mask[compound_indent] = true;
-
-                let last = nodes.clone().len();
-                let mut i = 1;
-                for node in nodes {
-                    if i == last {
-                        mask[compound_indent] = false;
-                    }
-
-                    indentation += 1;
-                    Node::print_tree(node, f)?;
-                    indentation -= 1;
-                    i += 1;
+            }
+            Node::LetDecl {
+                token,
+                name,
+                mutable,
+                ty,
+                value,
+                ..
+            } => {
+                let keyword = match (&token.kind, mutable) {
+                    (TokenKind::KEYWORD(lexer::KeywordKind::CONST), _) => "Const",
+                    (_, true) => "Let mut",
+                    (_, false) => "Let",
+                };
+                match ty {
+                    Some(ty) => self.indented_print(format!("{}: {}: {}", keyword, name, ty), f)?,
+                    None => self.indented_print(format!("{}: {}", keyword, name), f)?,
+                }
+                self.indentation += 1;
+                self.print_tree(value, f)?;
+                self.indentation -= 1;
+                Ok(())
+            }
+            Node::CompoundExpr { nodes } => {
+                self.indented_print("Compound:".to_string(), f)?;
+                self.print_children(nodes, f)
+            }
+            Node::Program { nodes } => {
+                self.indented_print("Program:".to_string(), f)?;
+                self.print_children(nodes, f)
+            }
+            Node::FnDecl {
+                name,
+                params,
+                ret_ty,
+                body,
+                ..
+            } => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|x| match x {
+                        Node::Variable { token, ty, .. } => format!("{}: {}", token, ty),
+                        _ => "?".to_string(),
+                    })
+                    .collect();
+                self.indented_print(
+                    format!("Fn: {} ({}) -> {}", name, params.join(", "), ret_ty),
+                    f,
+                )?;
+                self.indentation += 1;
+                self.print_tree(body, f)?;
+                self.indentation -= 1;
+                Ok(())
+            }
+            Node::Call { token, args, .. } => {
+                self.indented_print(format!("Call: {}", token), f)?;
+                self.print_children(args, f)
+            }
+            Node::Cast { node, ty, .. } => {
+                self.indented_print(format!("Cast: {}", ty), f)?;
+                self.indentation += 1;
+                self.print_tree(node, f)?;
+                self.indentation -= 1;
+                Ok(())
+            }
+            Node::IfStmt {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.indented_print("If:".to_string(), f)?;
+                let mut nodes = vec![*condition.clone(), *then.clone()];
+                if let Some(otherwise) = otherwise {
+                    nodes.push(*otherwise.clone());
+                }
+                self.print_children(&nodes, f)
+            }
+            Node::WhileStmt {
+                token,
+                label,
+                condition,
+                body,
+            } => {
+                self.indented_print(format!("{}{}:", label_prefix(label), token), f)?;
+                let mut nodes = Vec::new();
+                if let Some(condition) = condition {
+                    nodes.push(*condition.clone());
                 }
-                mask[compound_indent] = false;
-                return Ok(());
+                nodes.push(*body.clone());
+                self.print_children(&nodes, f)
+            }
+            Node::ForStmt {
+                label,
+                variable,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.indented_print(format!("{}For:", label_prefix(label)), f)?;
+                let nodes = [
+                    *variable.clone(),
+                    *start.clone(),
+                    *end.clone(),
+                    *body.clone(),
+                ];
+                self.print_children(&nodes, f)
+            }
+            Node::ForEach {
+                label,
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.indented_print(format!("{}For:", label_prefix(label)), f)?;
+                let nodes = [*variable.clone(), *iterable.clone(), *body.clone()];
+                self.print_children(&nodes, f)
+            }
+            Node::ArrayLiteral { elements, .. } => {
+                self.indented_print("Array:".to_string(), f)?;
+                self.print_children(elements, f)
+            }
+            Node::Index { array, index, .. } => {
+                self.indented_print("Index:".to_string(), f)?;
+                self.print_children(&[*array.clone(), *index.clone()], f)
+            }
+            Node::StructDecl { name, fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, ty)| format!("{}: {}", name, ty))
+                    .collect();
+                self.indented_print(format!("Struct: {} {{ {} }}", name, fields.join(", ")), f)
+            }
+            Node::StructLiteral { token, fields, .. } => {
+                self.indented_print(format!("Struct literal: {}", token), f)?;
+                // each value is printed under the name of its field
+                let literal_indent = self.indentation;
+                self.indentation += 1;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    self.mask[literal_indent] = i + 1 < fields.len();
+                    self.indented_print(format!("{}:", name), f)?;
+                    self.indentation += 1;
+                    self.print_tree(value, f)?;
+                    self.indentation -= 1;
+                }
+                self.mask[literal_indent] = false;
+                self.indentation -= 1;
+                Ok(())
+            }
+            Node::Field { node, name, .. } => {
+                self.indented_print(format!("Field: {}", name), f)?;
+                self.indentation += 1;
+                self.print_tree(node, f)?;
+                self.indentation -= 1;
+                Ok(())
+            }
+            Node::EnumDecl { name, variants, .. } => {
+                let variants: Vec<String> = variants
+                    .iter()
+                    .map(|(name, fields)| {
+                        let fields: Vec<String> = fields.iter().map(|x| x.to_string()).collect();
+                        format!("{}({})", name, fields.join(", "))
+                    })
+                    .collect();
+                self.indented_print(format!("Enum: {} {{ {} }}", name, variants.join(", ")), f)
+            }
+            Node::TypeDecl { token, name, ty } => {
+                let kind = match token.kind {
+                    TokenKind::KEYWORD(lexer::KeywordKind::NEWTYPE) => "Newtype",
+                    _ => "Type",
+                };
+                self.indented_print(format!("{}: {} = {}", kind, name, ty), f)
+            }
+            Node::Variant {
+                token, name, args, ..
+            } => {
+                self.indented_print(format!("Variant: {}::{}", token, name), f)?;
+                self.print_children(args, f)
+            }
+            Node::Match { node, arms, .. } => {
+                self.indented_print("Match:".to_string(), f)?;
+                // each arm's value is printed under its pattern
+                let match_indent = self.indentation;
+                self.indentation += 1;
+                self.mask[match_indent] = true;
+                self.print_tree(node, f)?;
+                for (i, (pattern, value)) in arms.iter().enumerate() {
+                    self.mask[match_indent] = i + 1 < arms.len();
+                    self.indented_print(format!("Arm: {}", pattern), f)?;
+                    self.indentation += 1;
+                    self.print_tree(value, f)?;
+                    self.indentation -= 1;
+                }
+                self.mask[match_indent] = false;
+                self.indentation -= 1;
+                Ok(())
+            }
+            Node::LoopControl { token, label } => match label {
+                Some(label) => self.indented_print(format!("{} {}", token, label), f),
+                None => self.indented_print(format!("{}", token), f),
             },
+            Node::GlobalDecl {
+                token,
+                name,
+                mutable,
+                ty,
+                value,
+            } => {
+                let keyword = match (&token.kind, mutable) {
+                    (TokenKind::KEYWORD(lexer::KeywordKind::CONST), _) => "Const",
+                    (_, true) => "Static mut",
+                    (_, false) => "Static",
+                };
+                self.indented_print(format!("{}: {}: {}", keyword, name, ty), f)?;
+                self.indentation += 1;
+                self.print_tree(value, f)?;
+                self.indentation -= 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn print_children(&mut self, nodes: &[Node], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compound_indent = self.indentation;
+        self.mask[compound_indent] = true;
+
+        for (i, node) in nodes.iter().enumerate() {
+            if i + 1 == nodes.len() {
+                self.mask[compound_indent] = false;
+            }
+
+            self.indentation += 1;
+            self.print_tree(node, f)?;
+            self.indentation -= 1;
         }
+        self.mask[compound_indent] = false;
+        Ok(())
     }
 
-    fn indented_print(data: String, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        unsafe {
-            if indentation != 0 {
-                for i in 0..indentation - 1 {
-                    if mask.len() <= i {
-                        mask.resize(32, false);
-                    }
-
-                    if mask[i] {
-                        write!(f, "|    ")?;
-                    } else {
-                        write!(f, "     ")?;
-                    }
+    fn indented_print(&mut self, data: String, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.indentation != 0 {
+            for &drawn in &self.mask[..self.indentation - 1] {
+                if drawn {
+                    write!(f, "|    ")?;
+                } else {
+                    write!(f, "     ")?;
                 }
-                write!(f, "|--> ")?;
             }
-            write!(f, "{}\n", data)
+            write!(f, "|--> ")?;
         }
+        writeln!(f, "{}", data)
     }
 }
@@ -0,0 +1,135 @@
+use crate::types::Type;
+
+// Functions the compiler provides itself. They are called like any other function, but some
+// can take values of more than one type, so each stage handles them on its own rather than
+// through a `fn` declaration. Their names can't be used for functions of our own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Builtin {
+    Print,
+    Println,
+    Eprint,
+    Eprintln,
+    ReadLine,
+    ReadInt,
+    Len,
+    Open,
+    Read,
+    Write,
+    Close,
+    ReadToString,
+    Alloc,
+    Free,
+    Vec,
+    Push,
+    Pop,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "print" => Some(Builtin::Print),
+            "println" => Some(Builtin::Println),
+            "eprint" => Some(Builtin::Eprint),
+            "eprintln" => Some(Builtin::Eprintln),
+            "read_line" => Some(Builtin::ReadLine),
+            "read_int" => Some(Builtin::ReadInt),
+            "len" => Some(Builtin::Len),
+            "open" => Some(Builtin::Open),
+            "read" => Some(Builtin::Read),
+            "write" => Some(Builtin::Write),
+            "close" => Some(Builtin::Close),
+            "read_to_string" => Some(Builtin::ReadToString),
+            "alloc" => Some(Builtin::Alloc),
+            "free" => Some(Builtin::Free),
+            "vec" => Some(Builtin::Vec),
+            "push" => Some(Builtin::Push),
+            "pop" => Some(Builtin::Pop),
+            _ => None,
+        }
+    }
+
+    // the fewest and most arguments it can be called with
+    pub fn arity(&self) -> (usize, usize) {
+        match self {
+            Builtin::Print | Builtin::Eprint | Builtin::Len | Builtin::Free | Builtin::Pop => {
+                (1, 1)
+            }
+            Builtin::Push => (2, 2),
+            Builtin::Println | Builtin::Eprintln => (0, 1),
+            _ => (self.params().len(), self.params().len()),
+        }
+    }
+
+    // the types of its parameters, for the ones that only take one type for each
+    pub fn params(&self) -> Vec<Type> {
+        match self {
+            Builtin::Print
+            | Builtin::Println
+            | Builtin::Eprint
+            | Builtin::Eprintln
+            | Builtin::Len
+            | Builtin::Free
+            | Builtin::Push
+            | Builtin::Pop => panic!("{:?} takes more than one type", self),
+            Builtin::ReadLine | Builtin::ReadInt | Builtin::Vec => vec![],
+            Builtin::Open => vec![Type::Str, Type::Str],
+            Builtin::Read | Builtin::Close | Builtin::ReadToString | Builtin::Alloc => {
+                vec![Type::I64]
+            }
+            Builtin::Write => vec![Type::I64, Type::Str],
+        }
+    }
+
+    // The type of the value it returns. What a `vec()` holds is whatever the vec it is used as
+    // holds, which the type checker fills in, and `pop` returns whatever its vec holds.
+    pub fn ty(&self) -> Type {
+        match self {
+            Builtin::Print
+            | Builtin::Println
+            | Builtin::Eprint
+            | Builtin::Eprintln
+            | Builtin::Free
+            | Builtin::Push
+            | Builtin::Pop => Type::Unit,
+            Builtin::Vec => Type::Vec(Box::new(Type::Unit)),
+            Builtin::Alloc => Type::Pointer(Box::new(Type::U8)),
+            Builtin::ReadLine | Builtin::ReadToString => Type::Str,
+            _ => Type::I64,
+        }
+    }
+
+    // the function that does the work, for the ones that are called like any other function
+    // rather than generated in place. Those starting `viper.` are in the runtime.
+    pub fn symbol(&self) -> Option<&'static str> {
+        match self {
+            Builtin::Print
+            | Builtin::Println
+            | Builtin::Eprint
+            | Builtin::Eprintln
+            | Builtin::Len
+            | Builtin::Free
+            | Builtin::Push
+            | Builtin::Pop => None,
+            Builtin::ReadLine => Some("viper.read_line"),
+            Builtin::ReadInt => Some("viper.read_int"),
+            Builtin::Open => Some("viper.open"),
+            Builtin::Read => Some("viper.read"),
+            Builtin::Write => Some("viper.write"),
+            Builtin::Close => Some("viper.close"),
+            Builtin::ReadToString => Some("viper.read_to_string"),
+            Builtin::Alloc => Some("viper.alloc"),
+            Builtin::Vec => Some("viper.vec_new"),
+        }
+    }
+
+    // the file descriptor it writes to, and whether it ends with a newline
+    pub fn output(&self) -> (i64, bool) {
+        match self {
+            Builtin::Print => (1, false),
+            Builtin::Println => (1, true),
+            Builtin::Eprint => (2, false),
+            Builtin::Eprintln => (2, true),
+            _ => panic!("{:?} doesn't print anything", self),
+        }
+    }
+}
@@ -1,9 +1,15 @@
 use crate::generator;
 use crate::lexer;
 use crate::parser;
+use crate::semantic;
+use crate::typeck;
 use std::fs;
+use std::io::Write;
 use std::process::*;
 
+// the functions generated code can call on, written once in assembly
+const RUNTIME: &str = include_str!("runtime.S");
+
 pub fn compile(filepath: String) {
     let input = fs::read_to_string(filepath.clone())
         .expect("Cannot read file")
@@ -16,32 +22,78 @@ pub fn compile(filepath: String) {
 
     println!("Running Lexer:");
     let mut lexer = lexer::Lexer::new(input, filepath.clone());
-    let tokens;
-    match lexer.tokenise() {
-        Ok(x) => tokens = x,
+    let tokens = match lexer.tokenise() {
+        Ok(x) => x,
         Err(x) => {
             println!("{}", x);
             exit(1);
         }
-    }
+    };
     for token in tokens.clone() {
         println!("{}", token);
     }
 
     println!("Running Parser:");
     let mut parser = parser::Parser::new(tokens, filepath.clone());
-    let ast;
-    match parser.parse() {
-        Ok(x) => ast = x,
+    let mut ast = match parser.parse() {
+        Ok(x) => x,
         Err(x) => {
             println!("{}", x);
             exit(1);
         }
-    }
+    };
     println!("{}", ast.clone());
 
+    // aliases and newtypes have to be known before anything can see through them
+    println!("Running Type Resolution:");
+    let types = parser.into_types();
+    if let Err(x) = types.resolve() {
+        println!("{}", x);
+        exit(1);
+    }
+
+    println!("Running Semantic Analysis:");
+    let mut analyser = semantic::Analyser::new(types);
+    if let Err(x) = analyser.analyse(&mut ast) {
+        println!("{}", x);
+        exit(1);
+    }
+
+    println!("Running Type Checker:");
+    let mut checker = typeck::TypeChecker::new(analyser.layouts().clone());
+    if let Err(x) = checker.check(&mut ast) {
+        println!("{}", x);
+        exit(1);
+    }
+
     println!("Running generator:");
-    let mut generator = generator::Generator::new(ast, output);
+    let mut generator = generator::Generator::new(ast, output.clone(), analyser.layouts().clone());
     generator.generate_code_block();
+    fs::OpenOptions::new()
+        .append(true)
+        .open(output)
+        .and_then(|mut x| x.write_all(RUNTIME.as_bytes()))
+        .expect("Cannot write runtime");
     println!("Done!");
 }
+
+// Every pass before code generation over `input`, for tests to look at what comes out of them,
+// or at the error one of them gives as it would be printed.
+#[cfg(test)]
+pub fn check(input: &str) -> Result<(crate::ast::Node, crate::layout::Layouts), String> {
+    let filepath = "test.vpr".to_string();
+    let tokens = lexer::Lexer::new(input.to_string(), filepath.clone())
+        .tokenise()
+        .map_err(|x| x.to_string())?;
+    let mut parser = parser::Parser::new(tokens, filepath);
+    let mut ast = parser.parse().map_err(|x| x.to_string())?;
+    let types = parser.into_types();
+    types.resolve().map_err(|x| x.to_string())?;
+    let mut analyser = semantic::Analyser::new(types);
+    analyser.analyse(&mut ast).map_err(|x| x.to_string())?;
+    let layouts = analyser.layouts().clone();
+    typeck::TypeChecker::new(layouts.clone())
+        .check(&mut ast)
+        .map_err(|x| x.to_string())?;
+    Ok((ast, layouts))
+}
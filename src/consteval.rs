@@ -0,0 +1,258 @@
+use crate::ast::*;
+use crate::error::*;
+use crate::lexer::*;
+use crate::types::Type;
+use std::collections::HashMap;
+
+// Works out the value of a constant expression at compile time. Values are kept the way the
+// generated code keeps them in %rax, sign or zero extended to 64 bits from their type, so the
+// answer is always the same as it would have been at runtime. An `f64` is kept as its bits.
+// `globals` holds the values of the `const`s declared so far.
+pub fn evaluate(node: &Node, globals: &HashMap<String, i64>) -> Result<i64, Error> {
+    match node {
+        Node::Primary(Token {
+            kind: TokenKind::INT(x),
+            ..
+        }) => Ok(normalise(x.value as i64, &x.ty())),
+        Node::Primary(Token {
+            kind: TokenKind::FLOAT(x),
+            ..
+        }) => Ok(x.to_bits() as i64),
+        Node::Primary(Token {
+            kind: TokenKind::CHAR(x),
+            ..
+        }) => Ok(*x as i64),
+        Node::Primary(Token {
+            kind: TokenKind::KEYWORD(KeywordKind::TRUE),
+            ..
+        }) => Ok(1),
+        Node::Primary(Token {
+            kind: TokenKind::KEYWORD(KeywordKind::FALSE | KeywordKind::NULL),
+            ..
+        }) => Ok(0),
+        Node::Variable {
+            token:
+                Token {
+                    kind: TokenKind::DECL_NAME(x),
+                    ..
+                },
+            ..
+        } => Ok(globals[x]),
+        Node::Cast { node, ty, .. } => {
+            let value = evaluate(node, globals)?;
+            match (node.ty().repr(), ty.repr()) {
+                (Type::F64, Type::F64) => Ok(value),
+                // `cvttsd2si` gives i64::MIN for anything it can't represent
+                (Type::F64, ty) => {
+                    let x = float(value);
+                    let limit = 9223372036854775808.0;
+                    let value = if (-limit..limit).contains(&x) {
+                        x as i64
+                    } else {
+                        i64::MIN
+                    };
+                    Ok(normalise(value, &ty))
+                }
+                (Type::U64, Type::F64) => Ok(((value as u64) as f64).to_bits() as i64),
+                (_, Type::F64) => Ok((value as f64).to_bits() as i64),
+                (_, ty) => Ok(normalise(value, &ty)),
+            }
+        }
+        Node::UnaryExpr { token, node } => {
+            let value = evaluate(node, globals)?;
+            let ty = node.ty();
+            match token.kind {
+                TokenKind::PLUS => Ok(value),
+                TokenKind::MINUS if ty.is_float() => Ok((-float(value)).to_bits() as i64),
+                TokenKind::MINUS => Ok(normalise(value.wrapping_neg(), &ty)),
+                TokenKind::BIT_NOT => Ok(normalise(!value, &ty)),
+                TokenKind::NOT => Ok((value == 0) as i64),
+                _ => panic!("unimplemented unary operator"),
+            }
+        }
+        Node::BinaryExpr { token, left, right } => {
+            let ty = Node::operand_type(left, right);
+            let signed = ty.is_signed();
+            let a = evaluate(left, globals)?;
+            // `&&` and `||` don't look at their right side if they don't need to
+            match token.kind {
+                TokenKind::AND if a == 0 => return Ok(0),
+                TokenKind::OR if a != 0 => return Ok(1),
+                _ => (),
+            }
+            let b = evaluate(right, globals)?;
+            if ty.is_float() {
+                return Ok(evaluate_float(token, float(a), float(b)));
+            }
+
+            let value = match token.kind {
+                TokenKind::PLUS => a.wrapping_add(b),
+                TokenKind::MINUS => a.wrapping_sub(b),
+                TokenKind::MUL => a.wrapping_mul(b),
+                TokenKind::DIV | TokenKind::MOD => {
+                    if b == 0 {
+                        return Err(evaluation_error("attempt to divide by zero", token));
+                    }
+                    match (signed, &token.kind) {
+                        // `idiv` faults on i64::MIN / -1 rather than wrapping
+                        (true, _) if a == i64::MIN && b == -1 => {
+                            return Err(evaluation_error("attempt to divide with overflow", token))
+                        }
+                        (true, TokenKind::DIV) => a / b,
+                        (true, _) => a % b,
+                        (false, TokenKind::DIV) => ((a as u64) / (b as u64)) as i64,
+                        (false, _) => ((a as u64) % (b as u64)) as i64,
+                    }
+                }
+                TokenKind::BIT_AND => a & b,
+                TokenKind::BIT_OR => a | b,
+                TokenKind::BIT_XOR => a ^ b,
+                // like the shift instructions, only the bottom 6 bits of the amount count
+                TokenKind::SHIFT_LEFT => a << (b & 63),
+                TokenKind::SHIFT_RIGHT if signed => a >> (b & 63),
+                TokenKind::SHIFT_RIGHT => ((a as u64) >> (b & 63)) as i64,
+                TokenKind::UNSIGNED_SHIFT_RIGHT => (zero_extend(a, &ty) as u64 >> (b & 63)) as i64,
+                TokenKind::DOUBLE_EQUALS => return Ok((a == b) as i64),
+                TokenKind::NOT_EQUALS => return Ok((a != b) as i64),
+                TokenKind::LESS_THAN if signed => return Ok((a < b) as i64),
+                TokenKind::LESS_EQUALS if signed => return Ok((a <= b) as i64),
+                TokenKind::GREATER_THAN if signed => return Ok((a > b) as i64),
+                TokenKind::GREATER_EQUALS if signed => return Ok((a >= b) as i64),
+                TokenKind::LESS_THAN => return Ok(((a as u64) < (b as u64)) as i64),
+                TokenKind::LESS_EQUALS => return Ok(((a as u64) <= (b as u64)) as i64),
+                TokenKind::GREATER_THAN => return Ok(((a as u64) > (b as u64)) as i64),
+                TokenKind::GREATER_EQUALS => return Ok(((a as u64) >= (b as u64)) as i64),
+                TokenKind::AND | TokenKind::OR => return Ok((b != 0) as i64),
+                _ => panic!("unimplemented binary operator"),
+            };
+            Ok(normalise(value, &ty))
+        }
+        _ => panic!("non constant expression left after semantic analysis"),
+    }
+}
+
+// like SSE, a float divided by zero is infinite rather than an error, and any comparison
+// with a NaN is false
+fn evaluate_float(token: &Token, a: f64, b: f64) -> i64 {
+    let value = match token.kind {
+        TokenKind::PLUS => a + b,
+        TokenKind::MINUS => a - b,
+        TokenKind::MUL => a * b,
+        TokenKind::DIV => a / b,
+        TokenKind::MOD => a % b,
+        TokenKind::DOUBLE_EQUALS => return (a == b) as i64,
+        TokenKind::NOT_EQUALS => return (a != b) as i64,
+        TokenKind::LESS_THAN => return (a < b) as i64,
+        TokenKind::LESS_EQUALS => return (a <= b) as i64,
+        TokenKind::GREATER_THAN => return (a > b) as i64,
+        TokenKind::GREATER_EQUALS => return (a >= b) as i64,
+        _ => panic!("unimplemented float operator"),
+    };
+    value.to_bits() as i64
+}
+
+fn float(value: i64) -> f64 {
+    f64::from_bits(value as u64)
+}
+
+// cuts `value` down to the width of `ty` and extends it back out to 64 bits
+pub fn normalise(value: i64, ty: &Type) -> i64 {
+    match ty.repr() {
+        Type::I8 => value as i8 as i64,
+        Type::I16 => value as i16 as i64,
+        Type::I32 => value as i32 as i64,
+        _ => zero_extend(value, ty),
+    }
+}
+
+fn zero_extend(value: i64, ty: &Type) -> i64 {
+    match ty.scalar_size() {
+        1 => value as u8 as i64,
+        2 => value as u16 as i64,
+        4 => value as u32 as i64,
+        _ => value,
+    }
+}
+
+fn evaluation_error(message: &str, token: &Token) -> Error {
+    Error::EvaluationError {
+        message: message.to_string(),
+        filepath: token.filepath.clone(),
+        coord: token.coord,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::check;
+
+    // the value of the global `name` in `source`, worked out like the type checker does
+    fn constant(source: &str, name: &str) -> i64 {
+        let (ast, _) = check(source).unwrap_or_else(|x| panic!("{}", x));
+        let nodes = match ast {
+            Node::Program { nodes } => nodes,
+            _ => panic!("parser didn't give a program"),
+        };
+        let mut globals = HashMap::new();
+        for node in nodes {
+            if let Node::GlobalDecl { name, value, .. } = node {
+                let value = evaluate(&value, &globals).unwrap_or_else(|x| panic!("{}", x));
+                if let TokenKind::DECL_NAME(x) = name.kind {
+                    globals.insert(x, value);
+                }
+            }
+        }
+        globals[name]
+    }
+
+    #[test]
+    fn wraps_to_the_width_of_the_type() {
+        assert_eq!(constant("const A: u8 = 1 << 9;", "A"), 0);
+        assert_eq!(constant("const A: u8 = 0 - 1;", "A"), 255);
+        assert_eq!(constant("const A: i8 = 127 + 1;", "A"), -128);
+        assert_eq!(constant("const A: i16 = 300 as i8 as i16;", "A"), 44);
+        assert_eq!(constant("const A: u32 = 4294967295 * 2;", "A"), 4294967294);
+    }
+
+    #[test]
+    fn shifts_like_the_instructions() {
+        // only the bottom 6 bits of the amount count
+        assert_eq!(constant("const A: i64 = 1 << 65;", "A"), 2);
+        assert_eq!(constant("const A: i32 = -8 >> 1;", "A"), -4);
+        // `>>>` shifts in zeros from the top of the type rather than of 64 bits
+        assert_eq!(constant("const A: i32 = -8 >>> 1;", "A"), 2147483644);
+        assert_eq!(constant("const A: u8 = 255 >> 4;", "A"), 15);
+    }
+
+    #[test]
+    fn compares_unsigned_values_as_unsigned() {
+        let source = "const A: u64 = 18446744073709551615;\nconst B: bool = A > 1;";
+        assert_eq!(constant(source, "B"), 1);
+        let source = "const A: i64 = -1;\nconst B: bool = A > 1;";
+        assert_eq!(constant(source, "B"), 0);
+    }
+
+    #[test]
+    fn casts_floats_like_cvttsd2si() {
+        assert_eq!(constant("const A: i64 = -2.9 as i64;", "A"), -2);
+        assert_eq!(constant("const A: i64 = 1e300 as i64;", "A"), i64::MIN);
+        assert_eq!(constant("const A: u8 = 257.5 as u8;", "A"), 1);
+        let source = "const A: u64 = 18446744073709551615;\nconst B: f64 = A as f64;";
+        let bits = constant(source, "B");
+        assert_eq!(f64::from_bits(bits as u64), 18446744073709551615.0);
+    }
+
+    #[test]
+    fn reports_what_would_fault_at_runtime() {
+        let error = check("const A: i64 = 1 / 0;").err().unwrap();
+        assert!(error.contains("attempt to divide by zero"), "{}", error);
+        let source = "const A: i64 = -9223372036854775807 - 1;\nconst B: i64 = A / -1;";
+        let error = check(source).err().unwrap();
+        assert!(
+            error.contains("attempt to divide with overflow"),
+            "{}",
+            error
+        );
+    }
+}
@@ -12,6 +12,57 @@ pub enum Error {
         filepath: String,
         coord: (u32, u32),
     },
+    UndeclaredNameError {
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+    },
+    LoopControlError {
+        keyword: String,
+        filepath: String,
+        coord: (u32, u32),
+    },
+    TypeError {
+        found: String,
+        expected: String,
+        filepath: String,
+        coord: (u32, u32),
+    },
+    // `coord` is where the assignment is and `declared` where the variable was declared
+    MutabilityError {
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+        declared: (u32, u32),
+    },
+    ConstantError {
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+    },
+    EvaluationError {
+        message: String,
+        filepath: String,
+        coord: (u32, u32),
+    },
+    RedefinitionError {
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+    },
+    // `from` is the type of the value and `to` the type it was cast to
+    CastError {
+        from: String,
+        to: String,
+        filepath: String,
+        coord: (u32, u32),
+    },
+    // `missing` are patterns for values that no arm of a `match` matches
+    ExhaustivenessError {
+        missing: Vec<String>,
+        filepath: String,
+        coord: (u32, u32),
+    },
 }
 
 impl fmt::Display for Error {
@@ -34,6 +85,62 @@ impl fmt::Display for Error {
                 filepath.clone(),
                 *coord,
             ),
+            Error::UndeclaredNameError {
+                name,
+                filepath,
+                coord,
+            } => Error::handle_undeclared_name_error(f, name.clone(), filepath.clone(), *coord),
+            Error::LoopControlError {
+                keyword,
+                filepath,
+                coord,
+            } => Error::handle_loop_control_error(f, keyword.clone(), filepath.clone(), *coord),
+            Error::TypeError {
+                found,
+                expected,
+                filepath,
+                coord,
+            } => Error::handle_type_error(
+                f,
+                found.clone(),
+                expected.clone(),
+                filepath.clone(),
+                *coord,
+            ),
+            Error::MutabilityError {
+                name,
+                filepath,
+                coord,
+                declared,
+            } => {
+                Error::handle_mutability_error(f, name.clone(), filepath.clone(), *coord, *declared)
+            }
+            Error::ConstantError {
+                name,
+                filepath,
+                coord,
+            } => Error::handle_constant_error(f, name.clone(), filepath.clone(), *coord),
+            Error::EvaluationError {
+                message,
+                filepath,
+                coord,
+            } => Error::handle_evaluation_error(f, message.clone(), filepath.clone(), *coord),
+            Error::RedefinitionError {
+                name,
+                filepath,
+                coord,
+            } => Error::handle_redefinition_error(f, name.clone(), filepath.clone(), *coord),
+            Error::CastError {
+                from,
+                to,
+                filepath,
+                coord,
+            } => Error::handle_cast_error(f, from.clone(), to.clone(), filepath.clone(), *coord),
+            Error::ExhaustivenessError {
+                missing,
+                filepath,
+                coord,
+            } => Error::handle_exhaustiveness_error(f, missing, filepath.clone(), *coord),
         }
     }
 }
@@ -45,8 +152,8 @@ impl Error {
         filepath: String,
         coord: (u32, u32),
     ) -> fmt::Result {
-        write!(f, "ERROR: Illegal Syntax Error\n")?;
-        write!(f, "  -->  {}:{}:{}\n", filepath, coord.0, coord.1)?;
+        writeln!(f, "ERROR: Illegal Syntax Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
 
         write!(f, "Found: {}", found)
         // TODO: Error messages with pointers
@@ -74,9 +181,131 @@ impl Error {
         filepath: String,
         coord: (u32, u32),
     ) -> fmt::Result {
-        write!(f, "ERROR: Invalid Syntax Error\n")?;
-        write!(f, "  -->  {}:{}:{}\n", filepath, coord.0, coord.1)?;
+        writeln!(f, "ERROR: Invalid Syntax Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        write!(f, "Found {}, Expected {}", found, expected)
+    }
+
+    fn handle_undeclared_name_error(
+        f: &mut fmt::Formatter<'_>,
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Undeclared Name Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        write!(f, "Cannot find `{}` in this scope", name)
+    }
+
+    fn handle_loop_control_error(
+        f: &mut fmt::Formatter<'_>,
+        keyword: String,
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Loop Control Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        write!(f, "`{}` outside of a loop", keyword)
+    }
+
+    fn handle_type_error(
+        f: &mut fmt::Formatter<'_>,
+        found: String,
+        expected: String,
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Type Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
 
         write!(f, "Found {}, Expected {}", found, expected)
     }
+
+    fn handle_mutability_error(
+        f: &mut fmt::Formatter<'_>,
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+        declared: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Mutability Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+        writeln!(f, "Cannot assign to immutable variable `{}`", name)?;
+
+        write!(
+            f,
+            "  `{}` declared here: {}:{}:{}",
+            name, filepath, declared.0, declared.1
+        )
+    }
+
+    fn handle_constant_error(
+        f: &mut fmt::Formatter<'_>,
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Constant Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        write!(f, "`{}` cannot be used in a constant expression", name)
+    }
+
+    fn handle_evaluation_error(
+        f: &mut fmt::Formatter<'_>,
+        message: String,
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Evaluation Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        write!(f, "{}", message)
+    }
+
+    fn handle_redefinition_error(
+        f: &mut fmt::Formatter<'_>,
+        name: String,
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Redefinition Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        write!(f, "`{}` is already defined", name)
+    }
+
+    fn handle_cast_error(
+        f: &mut fmt::Formatter<'_>,
+        from: String,
+        to: String,
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Cast Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        write!(f, "Cannot cast {} to {}", from, to)
+    }
+
+    // only the first few missing patterns are listed, in case there are a lot of them
+    fn handle_exhaustiveness_error(
+        f: &mut fmt::Formatter<'_>,
+        missing: &[String],
+        filepath: String,
+        coord: (u32, u32),
+    ) -> fmt::Result {
+        writeln!(f, "ERROR: Exhaustiveness Error")?;
+        writeln!(f, "  -->  {}:{}:{}", filepath, coord.0, coord.1)?;
+
+        let shown: Vec<String> = missing.iter().take(3).map(|x| format!("`{}`", x)).collect();
+        write!(f, "Patterns not covered: {}", shown.join(", "))?;
+        if missing.len() > shown.len() {
+            write!(f, " and {} more", missing.len() - shown.len())?;
+        }
+        Ok(())
+    }
 }
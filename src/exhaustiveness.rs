@@ -0,0 +1,412 @@
+use crate::ast::Pattern;
+use crate::consteval;
+use crate::error::Error;
+use crate::layout::Layouts;
+use crate::lexer::{Token, TokenKind};
+use crate::types::Type;
+use std::collections::HashMap;
+
+// A pattern boiled down to what matters for working out which values it matches. Literals and
+// ranges of integers, chars and bools are all inclusive ranges of the values they are kept as.
+#[derive(Clone)]
+enum Pat {
+    Wild,
+    Range(i128, i128),
+    Variant(usize, Vec<Pat>),
+}
+
+// Makes sure the patterns of a `match` on a `ty` between them match every value it could be,
+// otherwise the error lists patterns for the values they miss. `token` is the `match`.
+//
+// This is the usual way of doing it, as a matrix with a row per pattern: a value is missed if
+// none of the rows match it, and that is worked out a column at a time by splitting on what
+// the first column could be. For an enum that's each of its variants, and for a number the
+// ranges that come from cutting its whole range at the start and end of every range pattern,
+// so that every row either matches all of one of those or none of it.
+pub fn check(
+    patterns: &[&Pattern],
+    ty: &Type,
+    token: &Token,
+    layouts: &Layouts,
+) -> Result<(), Error> {
+    let mut rows = Vec::new();
+    for pattern in patterns {
+        rows.push(vec![lower(pattern, ty, layouts)?]);
+    }
+    let missing = missed(&rows, std::slice::from_ref(ty), layouts);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(Error::ExhaustivenessError {
+        missing: missing.iter().map(|x| show(&x[0], ty, layouts)).collect(),
+        filepath: token.filepath.clone(),
+        coord: token.coord,
+    })
+}
+
+fn lower(pattern: &Pattern, ty: &Type, layouts: &Layouts) -> Result<Pat, Error> {
+    match pattern {
+        Pattern::Wildcard(..) | Pattern::Binding(..) => Ok(Pat::Wild),
+        Pattern::Literal(node) => {
+            let value = value_of(consteval::evaluate(node, &HashMap::new())?, ty);
+            Ok(Pat::Range(value, value))
+        }
+        Pattern::Range {
+            token,
+            start,
+            end,
+            inclusive,
+        } => {
+            let start = value_of(consteval::evaluate(start, &HashMap::new())?, ty);
+            let mut end = value_of(consteval::evaluate(end, &HashMap::new())?, ty);
+            if !inclusive {
+                end -= 1;
+            }
+            if start > end {
+                return Err(Error::EvaluationError {
+                    message: format!("the range `{}` is empty", pattern),
+                    filepath: token.filepath.clone(),
+                    coord: token.coord,
+                });
+            }
+            Ok(Pat::Range(start, end))
+        }
+        Pattern::Variant { name, fields, .. } => {
+            let layout = match ty.repr() {
+                Type::Enum(x) => layouts.of_enum(&x),
+                _ => panic!("variant pattern on something other than an enum"),
+            };
+            let name = match &name.kind {
+                TokenKind::DECL_NAME(x) => x,
+                _ => panic!("variant pattern without a name"),
+            };
+            let (tag, variant) = layout
+                .variant(name)
+                .expect("variant pattern that was never declared");
+            let mut lowered = Vec::new();
+            for (field, declared) in fields.iter().zip(variant.fields.iter()) {
+                lowered.push(lower(field, &declared.ty, layouts)?);
+            }
+            Ok(Pat::Variant(tag, lowered))
+        }
+    }
+}
+
+// the code points kept for UTF-16 surrogate pairs, which no char can be
+const SURROGATES: (i128, i128) = (0xD800, 0xDFFF);
+
+// an evaluated value the way it compares, so a big `u64` isn't negative
+fn value_of(value: i64, ty: &Type) -> i128 {
+    if ty.repr() == Type::U64 {
+        value as u64 as i128
+    } else {
+        value as i128
+    }
+}
+
+// every value a number, char or bool can be, and `None` for anything else
+fn domain(ty: &Type) -> Option<(i128, i128)> {
+    match ty.repr() {
+        Type::Bool => Some((0, 1)),
+        Type::Char => Some((0, char::MAX as i128)),
+        ty if ty.is_integer() => Some(ty.int_range()),
+        _ => None,
+    }
+}
+
+// The values of the types in `tys` that none of `rows` match, as patterns with one column for
+// each type. Only the first column lists everything that's missed, the ones after it give one
+// example each.
+fn missed(rows: &[Vec<Pat>], tys: &[Type], layouts: &Layouts) -> Vec<Vec<Pat>> {
+    let (ty, rest) = match tys.split_first() {
+        Some(x) => x,
+        None if rows.is_empty() => return vec![Vec::new()],
+        None => return Vec::new(),
+    };
+    let heads: Vec<&Pat> = rows
+        .iter()
+        .map(|x| &x[0])
+        .filter(|x| !matches!(x, Pat::Wild))
+        .collect();
+    // a struct, str or f64 can only be matched by a name or `_`, so is split into nothing
+    let split = constructors(ty, &heads, layouts);
+    let opaque = split.is_none();
+    let constructors = split.unwrap_or_default();
+    let uncovered: Vec<Pat> = constructors
+        .iter()
+        .filter(|x| !heads.iter().any(|head| covers(head, x)))
+        .cloned()
+        .collect();
+
+    // if some of them aren't matched by anything in the first column, they are missed by
+    // whatever the rows starting with `_` miss in the rest of the columns
+    if opaque || !uncovered.is_empty() {
+        let rest_rows: Vec<Vec<Pat>> = rows
+            .iter()
+            .filter(|x| matches!(x[0], Pat::Wild))
+            .map(|x| x[1..].to_vec())
+            .collect();
+        let rest_missed = match missed(&rest_rows, rest, layouts).into_iter().next() {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+        let firsts = if opaque {
+            vec![Pat::Wild]
+        } else {
+            merge(uncovered, ty)
+        };
+        return firsts
+            .into_iter()
+            .map(|first| [vec![first], rest_missed.clone()].concat())
+            .collect();
+    }
+
+    // otherwise each of them is missed if the rows that match it miss something inside it
+    let mut missing = Vec::new();
+    for constructor in constructors {
+        let fields = field_types(ty, &constructor, layouts);
+        let specialised: Vec<Vec<Pat>> = rows
+            .iter()
+            .filter_map(|row| {
+                let inner = match &row[0] {
+                    Pat::Wild => vec![Pat::Wild; fields.len()],
+                    head if covers(head, &constructor) => match head {
+                        Pat::Variant(_, inner) => inner.clone(),
+                        _ => Vec::new(),
+                    },
+                    _ => return None,
+                };
+                Some([inner, row[1..].to_vec()].concat())
+            })
+            .collect();
+        let tys = [fields.clone(), rest.to_vec()].concat();
+        for found in missed(&specialised, &tys, layouts) {
+            let (inner, after) = found.split_at(fields.len());
+            let first = match constructor {
+                Pat::Variant(tag, ..) => Pat::Variant(tag, inner.to_vec()),
+                ref x => x.clone(),
+            };
+            missing.push([vec![first], after.to_vec()].concat());
+            // one example is enough inside a variant
+            if !inner.is_empty() {
+                break;
+            }
+        }
+    }
+    missing
+}
+
+// what the first column could be, split up finely enough that no pattern in `heads` matches
+// only part of one of them
+fn constructors(ty: &Type, heads: &[&Pat], layouts: &Layouts) -> Option<Vec<Pat>> {
+    if let Type::Enum(name) = ty.repr() {
+        return Some(
+            layouts
+                .of_enum(&name)
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(tag, x)| Pat::Variant(tag, vec![Pat::Wild; x.fields.len()]))
+                .collect(),
+        );
+    }
+    let (min, max) = domain(ty)?;
+    let mut cuts = vec![min, max + 1];
+    for head in heads {
+        if let Pat::Range(start, end) = head {
+            cuts.push(*start);
+            cuts.push(*end + 1);
+        }
+    }
+    // no char is a surrogate, so there is nothing there to match
+    let char = ty.repr() == Type::Char;
+    if char {
+        cuts.push(SURROGATES.0);
+        cuts.push(SURROGATES.1 + 1);
+    }
+    cuts.sort();
+    cuts.dedup();
+    Some(
+        cuts.windows(2)
+            .map(|x| (x[0], x[1] - 1))
+            .filter(|x| !(char && SURROGATES.0 <= x.0 && x.1 <= SURROGATES.1))
+            .map(|(start, end)| Pat::Range(start, end))
+            .collect(),
+    )
+}
+
+fn covers(head: &Pat, constructor: &Pat) -> bool {
+    match (head, constructor) {
+        (Pat::Wild, _) => true,
+        (Pat::Variant(a, ..), Pat::Variant(b, ..)) => a == b,
+        (Pat::Range(start, end), Pat::Range(a, b)) => start <= a && b <= end,
+        _ => false,
+    }
+}
+
+fn field_types(ty: &Type, constructor: &Pat, layouts: &Layouts) -> Vec<Type> {
+    match (ty.repr(), constructor) {
+        (Type::Enum(name), Pat::Variant(tag, ..)) => layouts.of_enum(&name).variants[*tag]
+            .fields
+            .iter()
+            .map(|x| x.ty.clone())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Joins up ranges that follow on from each other, so they are reported as one. For a char that
+// includes the ones either side of the surrogates.
+fn merge(missing: Vec<Pat>, ty: &Type) -> Vec<Pat> {
+    let follows = |end: i128, start: i128| {
+        end + 1 == start
+            || ty.repr() == Type::Char && end + 1 == SURROGATES.0 && start == SURROGATES.1 + 1
+    };
+    let mut merged: Vec<Pat> = Vec::new();
+    for pat in missing {
+        match (merged.last_mut(), &pat) {
+            (Some(Pat::Range(_, end)), Pat::Range(start, new_end)) if follows(*end, *start) => {
+                *end = *new_end;
+            }
+            _ => merged.push(pat),
+        }
+    }
+    merged
+}
+
+// a missed pattern the way it would be written in the program
+fn show(pat: &Pat, ty: &Type, layouts: &Layouts) -> String {
+    match pat {
+        Pat::Wild => "_".to_string(),
+        Pat::Range(start, end) if domain(ty) == Some((*start, *end)) => "_".to_string(),
+        Pat::Range(start, end) if start == end => show_value(*start, ty),
+        Pat::Range(start, end) => format!("{}..={}", show_value(*start, ty), show_value(*end, ty)),
+        Pat::Variant(tag, fields) => {
+            let name = match ty.repr() {
+                Type::Enum(x) => x,
+                _ => panic!("variant of something other than an enum"),
+            };
+            let layout = layouts.of_enum(&name);
+            let variant = &layout.variants[*tag];
+            let mut text = format!("{}::{}", name, variant.name);
+            if !fields.is_empty() {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .zip(variant.fields.iter())
+                    .map(|(x, field)| show(x, &field.ty, layouts))
+                    .collect();
+                text.push_str(&format!("({})", fields.join(", ")));
+            }
+            text
+        }
+    }
+}
+
+fn show_value(value: i128, ty: &Type) -> String {
+    match ty.repr() {
+        Type::Bool => (value != 0).to_string(),
+        Type::Char => match char::from_u32(value as u32) {
+            Some(x) => format!("{:?}", x),
+            None => format!("'\\u{{{:x}}}'", value),
+        },
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::check;
+
+    // what a `match` on an `x` of type `ty` with `arms` misses, as the error lists it, after
+    // `declarations` are made
+    fn missing(declarations: &str, ty: &str, arms: &str) -> Result<(), String> {
+        let source = format!(
+            "{}\nfn f(x: {}) -> i64 {{\n    return match x {{\n{}\n    }};\n}}",
+            declarations, ty, arms
+        );
+        match check(&source) {
+            Ok(_) => Ok(()),
+            Err(x) => Err(x.lines().last().unwrap_or_default().to_string()),
+        }
+    }
+
+    const NESTED: &str = "enum In { A(bool), B }\nenum Out { X(In, In), Y }";
+
+    #[test]
+    fn finds_what_nested_variants_miss() {
+        let arms = "Out::Y => 0, Out::X(In::B, _) => 1, Out::X(_, In::B) => 2, \
+                    Out::X(In::A(false), In::A(_)) => 3,";
+        assert_eq!(
+            missing(NESTED, "Out", arms),
+            Err("Patterns not covered: `Out::X(In::A(true), In::A(_))`".to_string())
+        );
+        let arms = "Out::Y => 0, Out::X(In::B, _) => 1, Out::X(_, In::B) => 2, \
+                    Out::X(In::A(false), In::A(_)) => 3, Out::X(In::A(true), In::A(b)) => 4,";
+        assert_eq!(missing(NESTED, "Out", arms), Ok(()));
+        assert_eq!(
+            missing(NESTED, "Out", "Out::Y => 0,"),
+            Err("Patterns not covered: `Out::X(_, _)`".to_string())
+        );
+    }
+
+    #[test]
+    fn finds_gaps_between_integer_ranges() {
+        assert_eq!(
+            missing("", "u8", "0..=9 => 0, 20..=255 => 1,"),
+            Err("Patterns not covered: `10..=19`".to_string())
+        );
+        assert_eq!(
+            missing("", "u8", "0 => 0, 2..=254 => 1,"),
+            Err("Patterns not covered: `1`, `255`".to_string())
+        );
+        // ranges that overlap or follow on from each other cover everything between them
+        assert_eq!(
+            missing("", "u8", "0..100 => 0, 50..=200 => 1, 201..=255 => 2,"),
+            Ok(())
+        );
+        assert_eq!(
+            missing("", "i8", "-128..=-1 => 0, 1..=127 => 1,"),
+            Err("Patterns not covered: `0`".to_string())
+        );
+        assert_eq!(
+            missing("", "u64", "0..=9223372036854775807 => 0,"),
+            Err("Patterns not covered: `9223372036854775808..=18446744073709551615`".to_string())
+        );
+        assert_eq!(
+            missing("", "bool", "true => 1,"),
+            Err("Patterns not covered: `false`".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_surrogates_out_of_chars() {
+        let arms = "'\\0'..='\\u{D7FF}' => 1, '\\u{E000}'..='\\u{10FFFF}' => 2,";
+        assert_eq!(missing("", "char", arms), Ok(()));
+        let arms = "'\\0'..='`' => 1, '{'..='\\u{10FFFF}' => 2,";
+        assert_eq!(
+            missing("", "char", arms),
+            Err("Patterns not covered: `'a'..='z'`".to_string())
+        );
+        // the ranges either side of the surrogates are missed as one
+        let arms = "'\\0'..='\\u{CFFF}' => 1, '\\u{E100}'..='\\u{10FFFF}' => 2,";
+        assert_eq!(
+            missing("", "char", arms),
+            Err("Patterns not covered: `'퀀'..='\\u{e0ff}'`".to_string())
+        );
+        assert_eq!(
+            missing("enum E { A(char), B }", "E", "E::B => 0,"),
+            Err("Patterns not covered: `E::A(_)`".to_string())
+        );
+    }
+
+    #[test]
+    fn only_splits_what_can_be_matched_on() {
+        let declarations = "struct P { a: i64 }";
+        assert_eq!(missing(declarations, "P", "p => 0,"), Ok(()));
+        assert_eq!(missing("", "f64", "_ => 0,"), Ok(()));
+        assert_eq!(
+            missing("", "i64", "5..5 => 0, _ => 1,"),
+            Err("the range `5..5` is empty".to_string())
+        );
+    }
+}
@@ -1,30 +1,219 @@
 use crate::ast::*;
+use crate::builtins::Builtin;
+use crate::consteval;
+use crate::layout::{self, Class, Layouts};
 use crate::lexer::*;
+use crate::types::Type;
+use std::collections::HashMap;
 use std::fs;
 use std::io::Write;
 
+// registers used for the first six integer arguments, as per the System V ABI
+const ARG_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+// and for the first eight float arguments
+const FLOAT_ARG_REGISTERS: [&str; 8] = [
+    "%xmm0", "%xmm1", "%xmm2", "%xmm3", "%xmm4", "%xmm5", "%xmm6", "%xmm7",
+];
+
+// the 1, 2, 4 and 8 byte names of each register we move values of different widths through
+const SIZED_REGISTERS: [[&str; 4]; 7] = [
+    ["%al", "%ax", "%eax", "%rax"],
+    ["%dil", "%di", "%edi", "%rdi"],
+    ["%sil", "%si", "%esi", "%rsi"],
+    ["%dl", "%dx", "%edx", "%rdx"],
+    ["%cl", "%cx", "%ecx", "%rcx"],
+    ["%r8b", "%r8w", "%r8d", "%r8"],
+    ["%r9b", "%r9w", "%r9d", "%r9"],
+];
+
+fn sized_register(reg: &str, size: i64) -> &'static str {
+    let names = SIZED_REGISTERS
+        .iter()
+        .find(|x| x[3] == reg)
+        .expect("no sized names for register");
+    match size {
+        1 => names[0],
+        2 => names[1],
+        4 => names[2],
+        _ => names[3],
+    }
+}
+
+// Where each argument of a call goes, as the registers each of its eightbytes is passed in.
+// Every eightbyte takes the next free register of the kind its class says, as long as there are
+// enough left for the whole argument. Anything that doesn't get registers is passed on the
+// stack in order, which is `None` here. `hidden` is set when %rdi is already taken by the
+// address a big struct or array is to be returned to.
+fn arg_registers(
+    types: &[Type],
+    hidden: bool,
+    layouts: &Layouts,
+) -> Vec<Option<Vec<&'static str>>> {
+    let mut ints = hidden as usize;
+    let mut floats = 0;
+    types
+        .iter()
+        .map(|ty| {
+            let classes = layouts.classify(ty)?;
+            let needed = classes.iter().filter(|x| **x == Class::Integer).count();
+            if ints + needed > ARG_REGISTERS.len()
+                || floats + classes.len() - needed > FLOAT_ARG_REGISTERS.len()
+            {
+                return None;
+            }
+            let registers = classes.iter().map(|class| match class {
+                Class::Integer => {
+                    ints += 1;
+                    ARG_REGISTERS[ints - 1]
+                }
+                Class::Sse => {
+                    floats += 1;
+                    FLOAT_ARG_REGISTERS[floats - 1]
+                }
+            });
+            Some(registers.collect())
+        })
+        .collect()
+}
+
+// the registers a struct or array is returned in, or `None` if it is too big and is returned
+// through memory instead
+fn ret_registers(ty: &Type, layouts: &Layouts) -> Option<Vec<&'static str>> {
+    let classes = layouts.classify(ty)?;
+    let (mut ints, mut floats) = (0, 0);
+    let registers = classes.iter().map(|class| match class {
+        Class::Integer => {
+            ints += 1;
+            ["%rax", "%rdx"][ints - 1]
+        }
+        Class::Sse => {
+            floats += 1;
+            ["%xmm0", "%xmm1"][floats - 1]
+        }
+    });
+    Some(registers.collect())
+}
+
+// how many of the `size` bytes of a value are in its `i`th eightbyte
+fn eightbyte_size(size: i64, i: usize) -> i64 {
+    (size - 8 * i as i64).min(8)
+}
+
+// how many eightbytes `generate_arg` pushes for a value of type `ty`
+fn pushed_eightbytes(ty: &Type, layouts: &Layouts) -> usize {
+    if ty.is_aggregate() {
+        layout::eightbytes(layouts.size(ty))
+    } else {
+        1
+    }
+}
+
+// the value of a literal in a pattern, the way it is kept in %rax
+fn pattern_value(node: &Node) -> i64 {
+    match consteval::evaluate(node, &HashMap::new()) {
+        Ok(x) => x,
+        Err(..) => panic!("pattern literal that can't be evaluated"),
+    }
+}
+
 pub struct Generator {
     ast: Node,
     file: fs::File,
+
+    // symbol of the function being generated, used for its return label
+    function: String,
+    ret_ty: Type,
+    // the slot holding the address to copy a struct or array that is returned to, for one
+    // too big to be returned in registers
+    hidden_ret: Option<i64>,
+    // number of values currently pushed onto the stack, so calls can keep %rsp 16 byte aligned
+    depth: usize,
+    // used to give every generated label a unique name
+    label_count: usize,
+    // loops we are currently inside as (source label, generated label, `depth` inside them),
+    // innermost last
+    loops: Vec<(Option<String>, usize, usize)>,
+    // every distinct string literal, the index being its `.L.str.N` label
+    strings: Vec<String>,
+    // the layouts the semantic pass gave every struct and enum
+    layouts: Layouts,
+}
+
+// The symbol a function or global of the program goes by. Everything but `main` has a `.` put
+// in its name, which nothing from C can have, so it can't clash with libc. The runtime's all
+// start `viper.` instead.
+fn symbol(name: &str) -> String {
+    if name == "main" {
+        name.to_string()
+    } else {
+        format!("user.{}", name)
+    }
+}
+
+// locals live in the function's frame and globals are found relative to %rip by their symbol
+fn address_of(variable: &Node) -> String {
+    match variable {
+        Node::Variable {
+            token,
+            global: true,
+            ..
+        } => match &token.kind {
+            TokenKind::DECL_NAME(x) => format!("{}(%rip)", symbol(x)),
+            _ => panic!("variable with a non name token"),
+        },
+        Node::Variable { offset, .. } => format!("-{}(%rbp)", offset),
+        _ => panic!("can only take the address of a variable"),
+    }
 }
 
 // TODO: Generator error handling
 impl Generator {
-    pub fn new(ast: Node, output: String) -> Self {
+    pub fn new(ast: Node, output: String, layouts: Layouts) -> Self {
         Self {
-            ast: ast,
+            ast,
             file: fs::File::create(output).expect("fail"),
+            function: String::new(),
+            ret_ty: Type::Unit,
+            hidden_ret: None,
+            depth: 0,
+            label_count: 0,
+            loops: Vec::new(),
+            strings: Vec::new(),
+            layouts,
         }
     }
 
     pub fn generate_code_block(&mut self) {
         self.emit(".text");
-        self.emit(".globl main");
-        self.emit("main:");
-        //self.emit("    push %rbp");
-        //self.emit("    mov %rbp, %rsp");
-
         self.generate_node(self.ast.clone());
+        self.generate_strings();
+    }
+
+    // the text of every string literal, zero terminated
+    fn generate_strings(&mut self) {
+        if self.strings.is_empty() {
+            return;
+        }
+        self.emit("    .pushsection .rodata");
+        for (i, text) in self.strings.clone().iter().enumerate() {
+            let bytes: Vec<String> = text.bytes().chain([0]).map(|x| x.to_string()).collect();
+            self.emit(format!(".L.str.{}:", i).as_str());
+            self.emit(format!("    .byte {}", bytes.join(",")).as_str());
+        }
+        self.emit("    .popsection");
+    }
+
+    // the label of `text`, which the same literal written twice shares
+    fn intern(&mut self, text: String) -> String {
+        let id = match self.strings.iter().position(|x| *x == text) {
+            Some(id) => id,
+            None => {
+                self.strings.push(text);
+                self.strings.len() - 1
+            }
+        };
+        format!(".L.str.{}", id)
     }
 
     fn generate_node(&mut self, node: Node) {
@@ -33,6 +222,998 @@ impl Generator {
             Node::UnaryExpr { .. } => self.generate_unary_expression(node),
             Node::BinaryExpr { .. } => self.generate_binary_expression(node),
             Node::CompoundExpr { nodes } => self.generate_compound_expression(nodes),
+            Node::Variable { .. } => {
+                let address = address_of(&node);
+                self.load(&node.ty(), address);
+            }
+            Node::ArrayLiteral {
+                elements,
+                ty,
+                offset,
+                ..
+            } => self.generate_array_literal(elements, ty, offset),
+            Node::Index { ref ty, .. } | Node::Field { ref ty, .. } => {
+                let ty = ty.clone();
+                self.generate_address(node);
+                self.load(&ty, "(%rax)".to_string());
+            }
+            Node::StructLiteral {
+                fields, ty, offset, ..
+            } => self.generate_struct_literal(fields, ty, offset),
+            Node::Variant {
+                name,
+                args,
+                ty,
+                offset,
+                ..
+            } => self.generate_variant(name, args, ty, offset),
+            Node::Match {
+                node, arms, offset, ..
+            } => self.generate_match(*node, arms, offset),
+            Node::StructDecl { .. } | Node::EnumDecl { .. } | Node::TypeDecl { .. } => (),
+            Node::LetDecl {
+                ty, value, offset, ..
+            } => {
+                let ty = ty.expect("let without a type after semantic analysis");
+                self.generate_node(*value);
+                self.normalise(&ty);
+                self.store_local(offset, &ty);
+            }
+            Node::Cast { node, ty, .. } => {
+                let from = node.ty();
+                self.generate_node(*node);
+                self.convert(&from, &ty);
+                self.normalise(&ty);
+            }
+            Node::Program { nodes } => self.generate_compound_expression(nodes),
+            Node::FnDecl {
+                name,
+                params,
+                ret_ty,
+                body,
+                frame_size,
+                ..
+            } => self.generate_function(name, params, ret_ty, *body, frame_size),
+            Node::Call {
+                token,
+                args,
+                ty,
+                offset,
+            } => self.generate_call(token, args, &ty, offset),
+            Node::IfStmt {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => self.generate_if(*condition, *then, otherwise.map(|x| *x)),
+            Node::WhileStmt {
+                label,
+                condition,
+                body,
+                ..
+            } => self.generate_while(label, condition.map(|x| *x), *body),
+            Node::ForStmt {
+                label,
+                variable,
+                start,
+                end,
+                body,
+                end_offset,
+                ..
+            } => self.generate_for(label, *variable, *start, *end, *body, end_offset),
+            Node::ForEach {
+                label,
+                variable,
+                iterable,
+                body,
+                offset,
+                ..
+            } => self.generate_for_each(label, *variable, *iterable, *body, offset),
+            Node::LoopControl { token, label } => self.generate_loop_control(token, label),
+            Node::GlobalDecl {
+                token,
+                name,
+                mutable,
+                ty,
+                value,
+            } => self.generate_global(token, name, mutable, ty, *value),
+        }
+    }
+
+    // Globals get put in the section that suits them and then we carry on in `.text`. Anything
+    // that can't be written to is read only, and anything that starts out as zero doesn't need
+    // to take up space in the executable.
+    fn generate_global(&mut self, token: Token, name: Token, mutable: bool, ty: Type, value: Node) {
+        let name = match name.kind {
+            TokenKind::DECL_NAME(x) => symbol(&x),
+            _ => panic!("global with a non name token"),
+        };
+        let mut data = Vec::new();
+        self.global_data(&ty, value, &mut data);
+
+        let zero = data
+            .iter()
+            .all(|x| matches!(x.1.as_deref(), None | Some("0")));
+        let section = match (mutable, zero) {
+            (false, _) => ".rodata",
+            (true, true) => ".bss",
+            (true, false) => ".data",
+        };
+        self.emit(format!("    .pushsection {}", section).as_str());
+        self.emit(format!("    .balign {}", self.layouts.align(&ty)).as_str());
+        self.emit(format!("{}:", name).as_str());
+        if section == ".bss" {
+            self.emit(format!("    .zero {}", self.layouts.size(&ty)).as_str());
+        } else {
+            for (size, value) in data {
+                let directive = match (size, &value) {
+                    (_, None) => ".zero",
+                    (1, _) => ".byte",
+                    (2, _) => ".short",
+                    (4, _) => ".long",
+                    _ => ".quad",
+                };
+                let value = value.unwrap_or(size.to_string());
+                self.emit(format!("    {} {}", directive, value).as_str());
+            }
+        }
+        self.emit("    .popsection");
+    }
+
+    // the size and value of each scalar a global is made of, in the order they're laid out,
+    // where a value of `None` is padding
+    fn global_data(&mut self, ty: &Type, value: Node, data: &mut Vec<(i64, Option<String>)>) {
+        let ty = &ty.repr();
+        if let (Type::Array(elem, ..), Node::ArrayLiteral { elements, .. }) = (ty, &value) {
+            for element in elements.clone() {
+                self.global_data(elem, element, data);
+            }
+            return;
+        }
+        if let (Type::Struct(name), Node::StructLiteral { fields, .. }) = (ty, &value) {
+            let layout = self.layouts.of(name);
+            let mut end = 0;
+            for field in layout.fields.iter() {
+                if field.offset > end {
+                    data.push((field.offset - end, None));
+                }
+                let value = fields
+                    .iter()
+                    .find(|x| x.0.kind == TokenKind::DECL_NAME(field.name.clone()))
+                    .expect("struct literal without a field after semantic analysis");
+                self.global_data(&field.ty, value.1.clone(), data);
+                end = field.offset + self.layouts.size(&field.ty);
+            }
+            if layout.size > end {
+                data.push((layout.size - end, None));
+            }
+            return;
+        }
+        if let (
+            Type::Enum(name),
+            Node::Variant {
+                name: variant,
+                args,
+                ..
+            },
+        ) = (ty, &value)
+        {
+            let layout = self.layouts.of_enum(name);
+            let (tag, variant) = match &variant.kind {
+                TokenKind::DECL_NAME(x) => layout.variant(x),
+                _ => None,
+            }
+            .expect("variant that was never declared");
+            data.push((layout::TAG.scalar_size(), Some(tag.to_string())));
+            let mut end = layout::TAG.scalar_size();
+            for (field, arg) in variant.fields.iter().zip(args.clone()) {
+                if field.offset > end {
+                    data.push((field.offset - end, None));
+                }
+                self.global_data(&field.ty, arg, data);
+                end = field.offset + self.layouts.size(&field.ty);
+            }
+            if layout.size > end {
+                data.push((layout.size - end, None));
+            }
+            return;
+        }
+        let value = match value.token().map(|x| x.kind.clone()) {
+            Some(TokenKind::INT(x)) => (x.value as i64).to_string(),
+            Some(TokenKind::FLOAT(x)) => (x.to_bits() as i64).to_string(),
+            Some(TokenKind::CHAR(x)) => (x as u32).to_string(),
+            Some(TokenKind::KEYWORD(KeywordKind::TRUE)) => "1".to_string(),
+            Some(TokenKind::KEYWORD(KeywordKind::FALSE)) => "0".to_string(),
+            Some(TokenKind::STR(x)) => self.intern(x),
+            _ => panic!("global initialiser left unevaluated after type checking"),
+        };
+        data.push((self.layouts.size(ty), Some(value)));
+    }
+
+    // The elements are worked out one at a time into the literal's own slot in the frame,
+    // leaving its address in %rax like any other array.
+    fn generate_array_literal(&mut self, elements: Vec<Node>, ty: Type, offset: i64) {
+        let elem = match ty {
+            Type::Array(elem, ..) => *elem,
+            _ => panic!("array literal without an array type"),
+        };
+        for (i, element) in elements.into_iter().enumerate() {
+            self.generate_node(element);
+            self.normalise(&elem);
+            self.store_local(offset - i as i64 * self.layouts.size(&elem), &elem);
+        }
+        self.emit(format!("    lea -{}(%rbp), %rax", offset).as_str());
+    }
+
+    // Like an array literal, the fields are worked out one at a time into the literal's slot,
+    // each at its offset in the layout.
+    fn generate_struct_literal(&mut self, fields: Vec<(Token, Node)>, ty: Type, offset: i64) {
+        let layout = match ty {
+            Type::Struct(x) => self.layouts.of(&x),
+            _ => panic!("struct literal without a struct type"),
+        };
+        for (name, value) in fields {
+            let field = match name.kind {
+                TokenKind::DECL_NAME(x) => layout.field(&x),
+                _ => None,
+            }
+            .expect("struct literal with a field that was never declared");
+            self.generate_node(value);
+            self.normalise(&field.ty);
+            self.store_local(offset - field.offset, &field.ty);
+        }
+        self.emit(format!("    lea -{}(%rbp), %rax", offset).as_str());
+    }
+
+    // Like a struct literal, the tag and then each field is stored into the variant's slot,
+    // leaving the rest of the enum as it was.
+    fn generate_variant(&mut self, name: Token, args: Vec<Node>, ty: Type, offset: i64) {
+        let layout = match ty {
+            Type::Enum(x) => self.layouts.of_enum(&x),
+            _ => panic!("variant without an enum type"),
+        };
+        let (tag, variant) = match name.kind {
+            TokenKind::DECL_NAME(x) => layout.variant(&x),
+            _ => None,
+        }
+        .expect("variant that was never declared");
+        self.emit(format!("    movl ${}, -{}(%rbp)", tag, offset).as_str());
+        for (arg, field) in args.into_iter().zip(variant.fields.iter()) {
+            self.generate_node(arg);
+            self.normalise(&field.ty);
+            self.store_local(offset - field.offset, &field.ty);
+        }
+        self.emit(format!("    lea -{}(%rbp), %rax", offset).as_str());
+    }
+
+    // Tries the pattern of each arm in turn, as a chain of compares that moves on to the next
+    // arm as soon as any part of one doesn't match. The value being matched is kept in the
+    // match's slot, as its address if it is an aggregate, and the value of the arm that matches
+    // is left in %rax. Names are bound as they're reached, which does no harm if the rest of
+    // the arm doesn't match, as they only exist inside it.
+    fn generate_match(&mut self, node: Node, arms: Vec<(Pattern, Node)>, offset: i64) {
+        let id = self.new_label();
+        let ty = node.ty();
+        self.generate_node(node);
+        self.emit(format!("    mov %rax, -{}(%rbp)", offset).as_str());
+        let place = if ty.is_aggregate() { Some(0) } else { None };
+        for (pattern, value) in arms {
+            let next = self.new_label();
+            self.generate_pattern(pattern, &ty, offset, place, next);
+            self.generate_node(value);
+            self.emit(format!("    jmp .L.end.{}", id).as_str());
+            self.emit(format!(".L.else.{}:", next).as_str());
+        }
+        self.emit(format!(".L.end.{}:", id).as_str());
+    }
+
+    // Jumps to `.L.else.{fail}` unless `pattern` matches the `ty` at `place`, which is `None`
+    // for the value in the match's slot and otherwise an offset from the address in it.
+    fn generate_pattern(
+        &mut self,
+        pattern: Pattern,
+        ty: &Type,
+        slot: i64,
+        place: Option<i64>,
+        fail: usize,
+    ) {
+        match pattern {
+            Pattern::Wildcard(..) => (),
+            Pattern::Binding(Node::Variable { offset, .. }) => {
+                self.load_place(ty, slot, place);
+                self.store_local(offset, ty);
+            }
+            Pattern::Binding(..) => panic!("binding that isn't a variable"),
+            Pattern::Literal(node) => {
+                let value = pattern_value(&node);
+                self.load_place(ty, slot, place);
+                self.compare_immediate(value);
+                self.emit(format!("    jne .L.else.{}", fail).as_str());
+            }
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let start = pattern_value(&start);
+                let end = pattern_value(&end);
+                let (below, above) = match (ty.is_signed(), inclusive) {
+                    (true, true) => ("jl", "jg"),
+                    (true, false) => ("jl", "jge"),
+                    (false, true) => ("jb", "ja"),
+                    (false, false) => ("jb", "jae"),
+                };
+                self.load_place(ty, slot, place);
+                self.compare_immediate(start);
+                self.emit(format!("    {} .L.else.{}", below, fail).as_str());
+                self.compare_immediate(end);
+                self.emit(format!("    {} .L.else.{}", above, fail).as_str());
+            }
+            Pattern::Variant { name, fields, .. } => {
+                let layout = match ty.repr() {
+                    Type::Enum(x) => self.layouts.of_enum(&x),
+                    _ => panic!("variant pattern on a non enum"),
+                };
+                let (tag, variant) = match name.kind {
+                    TokenKind::DECL_NAME(x) => layout.variant(&x),
+                    _ => None,
+                }
+                .expect("variant pattern that was never declared");
+                let start = place.expect("enum matched without its address");
+                self.emit(format!("    mov -{}(%rbp), %rax", slot).as_str());
+                self.emit(format!("    cmpl ${}, {}(%rax)", tag, start).as_str());
+                self.emit(format!("    jne .L.else.{}", fail).as_str());
+                for (field, declared) in fields.into_iter().zip(variant.fields.iter()) {
+                    let place = Some(start + declared.offset);
+                    self.generate_pattern(field, &declared.ty, slot, place, fail);
+                }
+            }
+        }
+    }
+
+    // loads the `ty` at `place` in what a match is matching on, the same way as
+    // `generate_pattern` finds it
+    fn load_place(&mut self, ty: &Type, slot: i64, place: Option<i64>) {
+        match place {
+            None => self.load(ty, format!("-{}(%rbp)", slot)),
+            Some(offset) => {
+                self.emit(format!("    mov -{}(%rbp), %rax", slot).as_str());
+                self.load(ty, format!("{}(%rax)", offset));
+            }
+        }
+    }
+
+    // compares %rax against `value`, which only fits in the instruction if it fits in 32 bits
+    fn compare_immediate(&mut self, value: i64) {
+        if value == value as i32 as i64 {
+            self.emit(format!("    cmp ${}, %rax", value).as_str());
+        } else {
+            self.emit(format!("    movabs ${}, %rcx", value).as_str());
+            self.emit("    cmp %rcx, %rax");
+        }
+    }
+
+    // Leaves the address of a place in %rax, which is a variable, a field, an element of an
+    // array or what a pointer points at. That is all that can be assigned to or have its
+    // address taken, and using one as a value loads it from there. Indexes are checked against
+    // the array's length before they're used, and one that's out of bounds has the runtime
+    // report where it was and abort.
+    fn generate_address(&mut self, node: Node) {
+        match node {
+            Node::Variable { .. } => {
+                self.emit(format!("    lea {}, %rax", address_of(&node)).as_str())
+            }
+            // a pointer is already the address of what it points at
+            Node::UnaryExpr { token, node } if token.kind == TokenKind::MUL => {
+                self.generate_node(*node)
+            }
+            Node::Index {
+                array, index, ty, ..
+            } => {
+                // where the length and the elements are, given the address of the array or
+                // header of the vec in %rdi
+                let (len, elements) = match array.ty().repr() {
+                    Type::Array(_, len) => (format!("${}", len), "%rdi"),
+                    Type::Vec(..) => ("8(%rdi)".to_string(), "(%rdi)"),
+                    _ => panic!("index into a non array"),
+                };
+                let token = index.token().expect("index without a token").clone();
+                let location = self.intern(format!(
+                    "{}:{}:{}",
+                    token.filepath, token.coord.0, token.coord.1
+                ));
+                let id = self.new_label();
+
+                // an array evaluates to its address, and a vec to the address of its header
+                self.generate_node(*array);
+                self.push("%rax");
+                self.generate_node(*index);
+                self.pop("%rdi");
+                self.emit(format!("    mov {}, %rcx", len).as_str());
+                self.emit("    cmp %rcx, %rax");
+                self.emit(format!("    jb .L.end.{}", id).as_str());
+                self.emit("    mov %rax, %rsi");
+                self.emit("    mov %rcx, %rdx");
+                self.emit(format!("    lea {}(%rip), %rdi", location).as_str());
+                self.call_aligned("viper.bounds_fail");
+                self.emit(format!(".L.end.{}:", id).as_str());
+                self.emit(format!("    imul ${}, %rax", self.layouts.size(&ty)).as_str());
+                self.emit(format!("    add {}, %rax", elements).as_str());
+            }
+            Node::Field { node, name, .. } => {
+                let offset = match (node.ty().repr(), name.kind) {
+                    (Type::Struct(x), TokenKind::DECL_NAME(name)) => {
+                        self.layouts
+                            .of(&x)
+                            .field(&name)
+                            .expect("field that was never declared")
+                            .offset
+                    }
+                    _ => panic!("field of a non struct"),
+                };
+                // a struct evaluates to its address
+                self.generate_node(*node);
+                self.emit(format!("    add ${}, %rax", offset).as_str());
+            }
+            _ => panic!("can only take the address of a place"),
+        }
+    }
+
+    fn generate_while(&mut self, label: Option<Token>, condition: Option<Node>, body: Node) {
+        let id = self.new_label();
+
+        self.emit(format!(".L.continue.{}:", id).as_str());
+        if let Some(condition) = condition {
+            self.generate_node(condition);
+            self.emit("    cmp $0, %rax");
+            self.emit(format!("    je .L.break.{}", id).as_str());
+        }
+        self.generate_loop_body(label, id, body);
+        self.emit(format!("    jmp .L.continue.{}", id).as_str());
+        self.emit(format!(".L.break.{}:", id).as_str());
+    }
+
+    fn generate_for(
+        &mut self,
+        label: Option<Token>,
+        variable: Node,
+        start: Node,
+        end: Node,
+        body: Node,
+        end_offset: i64,
+    ) {
+        let id = self.new_label();
+        let (offset, ty) = match variable {
+            Node::Variable { offset, ty, .. } => (offset, ty),
+            _ => panic!("for loop over a non variable"),
+        };
+
+        self.generate_node(start);
+        self.normalise(&ty);
+        self.store_local(offset, &ty);
+        self.generate_node(end);
+        self.normalise(&ty);
+        self.store_local(end_offset, &ty);
+
+        self.emit(format!(".L.begin.{}:", id).as_str());
+        self.load(&ty, format!("-{}(%rbp)", end_offset));
+        self.emit("    mov %rax, %rdi");
+        self.load(&ty, format!("-{}(%rbp)", offset));
+        self.emit("    cmp %rdi, %rax");
+        let jump = if ty.is_signed() { "jge" } else { "jae" };
+        self.emit(format!("    {} .L.break.{}", jump, id).as_str());
+        self.generate_loop_body(label, id, body);
+        self.emit(format!(".L.continue.{}:", id).as_str());
+        self.load(&ty, format!("-{}(%rbp)", offset));
+        self.emit("    add $1, %rax");
+        self.store_local(offset, &ty);
+        self.emit(format!("    jmp .L.begin.{}", id).as_str());
+        self.emit(format!(".L.break.{}:", id).as_str());
+    }
+
+    // Copies each element of an array or vec into the loop variable in turn. The length of a
+    // vec is read again each time round, so anything pushed to it inside the loop is looped
+    // over as well.
+    fn generate_for_each(
+        &mut self,
+        label: Option<Token>,
+        variable: Node,
+        iterable: Node,
+        body: Node,
+        offset: i64,
+    ) {
+        let id = self.new_label();
+        let (variable, elem) = match variable {
+            Node::Variable { offset, ty, .. } => (offset, ty),
+            _ => panic!("for loop over a non variable"),
+        };
+        let (len, elements) = match iterable.ty().repr() {
+            Type::Array(_, len) => (format!("${}", len), "%rdi"),
+            Type::Vec(..) => ("8(%rdi)".to_string(), "(%rdi)"),
+            _ => panic!("for loop over a non array"),
+        };
+        let index = offset + 8;
+
+        self.generate_node(iterable);
+        self.store_local(offset, &Type::I64);
+        self.emit(format!("    movq $0, -{}(%rbp)", index).as_str());
+
+        self.emit(format!(".L.begin.{}:", id).as_str());
+        self.emit(format!("    mov -{}(%rbp), %rdi", offset).as_str());
+        self.emit(format!("    mov -{}(%rbp), %rax", index).as_str());
+        self.emit(format!("    mov {}, %rcx", len).as_str());
+        self.emit("    cmp %rcx, %rax");
+        self.emit(format!("    jae .L.break.{}", id).as_str());
+        self.emit(format!("    imul ${}, %rax", self.layouts.size(&elem)).as_str());
+        self.emit(format!("    add {}, %rax", elements).as_str());
+        self.load(&elem, "(%rax)".to_string());
+        self.store_local(variable, &elem);
+        self.generate_loop_body(label, id, body);
+        self.emit(format!(".L.continue.{}:", id).as_str());
+        self.emit(format!("    addq $1, -{}(%rbp)", index).as_str());
+        self.emit(format!("    jmp .L.begin.{}", id).as_str());
+        self.emit(format!(".L.break.{}:", id).as_str());
+    }
+
+    fn generate_loop_body(&mut self, label: Option<Token>, id: usize, body: Node) {
+        let label = label.map(|x| match x.kind {
+            TokenKind::DECL_NAME(x) => x,
+            _ => panic!("label with a non name token"),
+        });
+        self.loops.push((label, id, self.depth));
+        self.generate_node(body);
+        self.loops.pop();
+    }
+
+    fn generate_loop_control(&mut self, token: Token, label: Option<Token>) {
+        let target = match label.map(|x| x.kind) {
+            Some(TokenKind::DECL_NAME(x)) => {
+                self.loops.iter().rev().find(|l| l.0 == Some(x.clone()))
+            }
+            _ => self.loops.last(),
+        };
+        let (_, id, depth) = target.expect("loop control outside of a loop").clone();
+
+        // inside an expression, like a `match` arm that is an argument, there can still be
+        // values pushed that the loop doesn't know about
+        if self.depth > depth {
+            self.emit(format!("    add ${}, %rsp", (self.depth - depth) * 8).as_str());
+        }
+        match token.kind {
+            TokenKind::KEYWORD(KeywordKind::BREAK) => {
+                self.emit(format!("    jmp .L.break.{}", id).as_str())
+            }
+            _ => self.emit(format!("    jmp .L.continue.{}", id).as_str()),
+        }
+    }
+
+    fn generate_if(&mut self, condition: Node, then: Node, otherwise: Option<Node>) {
+        let label = self.new_label();
+
+        self.generate_node(condition);
+        self.emit("    cmp $0, %rax");
+        self.emit(format!("    je .L.else.{}", label).as_str());
+        self.generate_node(then);
+        self.emit(format!("    jmp .L.end.{}", label).as_str());
+        self.emit(format!(".L.else.{}:", label).as_str());
+        if let Some(otherwise) = otherwise {
+            self.generate_node(otherwise);
+        }
+        self.emit(format!(".L.end.{}:", label).as_str());
+    }
+
+    fn generate_function(
+        &mut self,
+        name: Token,
+        params: Vec<Node>,
+        ret_ty: Type,
+        body: Node,
+        frame_size: i64,
+    ) {
+        self.function = match name.kind {
+            TokenKind::DECL_NAME(x) => symbol(&x),
+            _ => panic!("function with a non name token"),
+        };
+        self.depth = 0;
+        // the address to return a big struct or array to gets a slot below everything else
+        let mut frame_size = frame_size;
+        self.hidden_ret = None;
+        if ret_ty.is_aggregate() && ret_registers(&ret_ty, &self.layouts).is_none() {
+            frame_size += 16;
+            self.hidden_ret = Some(frame_size);
+        }
+        self.ret_ty = ret_ty;
+
+        self.emit(format!(".globl {}", self.function).as_str());
+        self.emit(format!("{}:", self.function).as_str());
+        self.emit("    push %rbp");
+        self.emit("    mov %rsp, %rbp");
+        self.emit(format!("    sub ${}, %rsp", frame_size).as_str());
+        if let Some(slot) = self.hidden_ret {
+            self.emit(format!("    mov %rdi, -{}(%rbp)", slot).as_str());
+        }
+
+        // parameters are copied into their slots, the ones in registers first, since copying
+        // the ones the caller pushed above the return address uses some of those registers
+        let types: Vec<Type> = params.iter().map(|x| x.ty()).collect();
+        let mut stack_params = Vec::new();
+        for (param, regs) in params.iter().zip(arg_registers(
+            &types,
+            self.hidden_ret.is_some(),
+            &self.layouts,
+        )) {
+            if let Node::Variable { offset, ty, .. } = param {
+                match regs {
+                    Some(regs) if ty.is_aggregate() => {
+                        for (i, reg) in regs.into_iter().enumerate() {
+                            let reg = if reg.starts_with("%xmm") {
+                                self.emit(format!("    movq {}, %rax", reg).as_str());
+                                "%rax"
+                            } else {
+                                reg
+                            };
+                            let size = eightbyte_size(self.layouts.size(ty), i);
+                            self.store_bytes(reg, size, 8 * i as i64 - offset, "%rbp");
+                        }
+                    }
+                    Some(regs) if ty.is_float() => {
+                        self.emit(format!("    movsd {}, -{}(%rbp)", regs[0], offset).as_str())
+                    }
+                    Some(regs) => self.emit(
+                        format!(
+                            "    mov {}, -{}(%rbp)",
+                            sized_register(regs[0], self.layouts.size(ty)),
+                            offset
+                        )
+                        .as_str(),
+                    ),
+                    None => stack_params.push((*offset, ty.clone())),
+                }
+            }
+        }
+        let mut stack_offset = 16;
+        for (offset, ty) in stack_params {
+            if ty.is_aggregate() {
+                self.emit(format!("    lea {}(%rbp), %rsi", stack_offset).as_str());
+                self.emit(format!("    lea -{}(%rbp), %rdi", offset).as_str());
+                self.copy(self.layouts.size(&ty));
+            } else {
+                self.emit(format!("    mov {}(%rbp), %rax", stack_offset).as_str());
+                self.store_local(offset, &ty);
+            }
+            stack_offset += 8 * layout::eightbytes(self.layouts.size(&ty)) as i64;
+        }
+
+        self.generate_node(body);
+
+        // falling off the end of a function returns 0
+        self.emit("    mov $0, %rax");
+        self.emit(format!(".L.return.{}:", self.function).as_str());
+        self.emit("    mov %rbp, %rsp");
+        self.emit("    pop %rbp");
+        self.emit("    ret");
+    }
+
+    fn generate_call(&mut self, token: Token, args: Vec<Node>, ty: &Type, offset: i64) {
+        let mut name = match &token.kind {
+            TokenKind::DECL_NAME(x) => x.clone(),
+            _ => panic!("call with a non name token"),
+        };
+        if let Some(builtin) = Builtin::from_name(&name) {
+            match builtin.symbol() {
+                Some(symbol) => name = symbol.to_string(),
+                None => {
+                    return match builtin {
+                        Builtin::Len => self.generate_len(args),
+                        Builtin::Free => self.generate_free(args),
+                        Builtin::Push => self.generate_push(args),
+                        Builtin::Pop => self.generate_pop(&token, args, ty, offset),
+                        _ => self.generate_print(builtin, args),
+                    }
+                }
+            }
+        } else {
+            name = symbol(&name);
+        }
+
+        // Arguments are worked out first to last onto the stack. Then the ones passed on the
+        // stack are copied below them in order, padded so %rsp stays 16 byte aligned, and the
+        // rest are loaded into their registers. A struct or array too big to be returned in
+        // registers is returned to the call's slot, whose address is passed in %rdi.
+        let hidden = ty.is_aggregate() && ret_registers(ty, &self.layouts).is_none();
+        let types: Vec<Type> = args.iter().map(|x| x.ty()).collect();
+        let regs = arg_registers(&types, hidden, &self.layouts);
+        let counts: Vec<usize> = types
+            .iter()
+            .map(|x| pushed_eightbytes(x, &self.layouts))
+            .collect();
+        for arg in args {
+            self.generate_arg(arg);
+        }
+        let pushed: usize = counts.iter().sum();
+        let stack_count: usize = regs
+            .iter()
+            .zip(counts.iter())
+            .filter(|x| x.0.is_none())
+            .map(|x| x.1)
+            .sum();
+        let padding = (self.depth + stack_count) % 2 == 1;
+        let below = stack_count + padding as usize;
+        if below > 0 {
+            self.emit(format!("    sub ${}, %rsp", below * 8).as_str());
+            self.depth += below;
+        }
+        // where each argument was pushed, counting eightbytes up from the last one
+        let mut start = pushed;
+        let mut outgoing = 0;
+        for (regs, count) in regs.iter().zip(counts.iter()) {
+            start -= count;
+            let from = 8 * (below + start) as i64;
+            match regs {
+                Some(regs) => {
+                    for (i, reg) in regs.iter().enumerate() {
+                        let op = if reg.starts_with("%xmm") {
+                            "movq"
+                        } else {
+                            "mov"
+                        };
+                        let source = from + 8 * i as i64;
+                        self.emit(format!("    {} {}(%rsp), {}", op, source, reg).as_str());
+                    }
+                }
+                None => {
+                    for i in 0..*count {
+                        self.emit(format!("    mov {}(%rsp), %rax", from + 8 * i as i64).as_str());
+                        self.emit(format!("    mov %rax, {}(%rsp)", 8 * outgoing).as_str());
+                        outgoing += 1;
+                    }
+                }
+            }
+        }
+        if hidden {
+            self.emit(format!("    lea -{}(%rbp), %rdi", offset).as_str());
+        }
+
+        self.emit(format!("    call {}", name).as_str());
+
+        let cleanup = below + pushed;
+        if cleanup > 0 {
+            self.emit(format!("    add ${}, %rsp", cleanup * 8).as_str());
+            self.depth -= cleanup;
+        }
+
+        if ty.is_aggregate() {
+            return self.store_returned(ty, offset);
+        }
+        if ty.is_float() {
+            self.emit("    movq %xmm0, %rax");
+        }
+        // only the low bits of a narrow return value are defined by the ABI
+        self.normalise(ty);
+    }
+
+    // Pushes the value of an argument, laid out the way it is in memory, so a struct or array is
+    // copied onto the stack whole and has its first eightbyte at %rsp.
+    fn generate_arg(&mut self, arg: Node) {
+        let ty = arg.ty();
+        self.generate_node(arg);
+        if !ty.is_aggregate() {
+            return self.push("%rax");
+        }
+
+        let size = self.layouts.size(&ty);
+        self.emit(
+            format!(
+                "    sub ${}, %rsp",
+                pushed_eightbytes(&ty, &self.layouts) * 8
+            )
+            .as_str(),
+        );
+        self.depth += pushed_eightbytes(&ty, &self.layouts);
+        self.emit("    mov %rsp, %rdi");
+        self.emit("    mov %rax, %rsi");
+        self.copy(size);
+    }
+
+    // Copies a struct or array returned in registers into the call's slot, leaving its address
+    // in %rax. One returned through memory is already there.
+    fn store_returned(&mut self, ty: &Type, offset: i64) {
+        if let Some(regs) = ret_registers(ty, &self.layouts) {
+            // %rax goes first, since the xmm registers are moved through it
+            let mut parts: Vec<(usize, &str)> = regs.into_iter().enumerate().collect();
+            parts.sort_by_key(|x| x.1 != "%rax");
+            for (i, reg) in parts {
+                let reg = if reg.starts_with("%xmm") {
+                    self.emit(format!("    movq {}, %rax", reg).as_str());
+                    "%rax"
+                } else {
+                    reg
+                };
+                let size = eightbyte_size(self.layouts.size(ty), i);
+                self.store_bytes(reg, size, 8 * i as i64 - offset, "%rbp");
+            }
+        }
+        self.emit(format!("    lea -{}(%rbp), %rax", offset).as_str());
+    }
+
+    // A struct or array is returned in registers the same way it would be passed, or if it is
+    // too big, copied to the memory the caller passed the address of, which goes in %rax.
+    fn return_aggregate(&mut self) {
+        let ty = self.ret_ty.clone();
+        match ret_registers(&ty, &self.layouts) {
+            Some(regs) => {
+                self.emit("    mov %rax, %rsi");
+                // %rax goes last, since the others are loaded through it
+                let mut parts: Vec<(usize, &str)> = regs.into_iter().enumerate().collect();
+                parts.sort_by_key(|x| x.1 == "%rax");
+                for (i, reg) in parts {
+                    self.load_bytes(
+                        eightbyte_size(self.layouts.size(&ty), i),
+                        8 * i as i64,
+                        "%rsi",
+                    );
+                    if reg.starts_with("%xmm") {
+                        self.emit(format!("    movq %rax, {}", reg).as_str());
+                    } else if reg != "%rax" {
+                        self.emit(format!("    mov %rax, {}", reg).as_str());
+                    }
+                }
+            }
+            None => {
+                let slot = self.hidden_ret.expect("no slot for the return address");
+                self.emit(format!("    mov -{}(%rbp), %rdi", slot).as_str());
+                self.emit("    mov %rax, %rsi");
+                self.copy(self.layouts.size(&ty));
+                self.emit(format!("    mov -{}(%rbp), %rax", slot).as_str());
+            }
+        }
+    }
+
+    // the length of a `str` is counted by libc's `strlen`, and an array's is part of its type
+    fn generate_len(&mut self, args: Vec<Node>) {
+        let arg = args.into_iter().next().expect("len without an argument");
+        let ty = arg.ty();
+        self.generate_node(arg);
+        match ty.repr() {
+            Type::Array(_, len) => self.emit(format!("    mov ${}, %rax", len).as_str()),
+            Type::Vec(..) => self.emit("    mov 8(%rax), %rax"),
+            _ => {
+                self.emit("    mov %rax, %rdi");
+                self.call_aligned("strlen");
+            }
+        }
+    }
+
+    // a vec has its elements freed along with it, by the runtime
+    fn generate_free(&mut self, args: Vec<Node>) {
+        let arg = args.into_iter().next().expect("free without an argument");
+        let function = match arg.ty().repr() {
+            Type::Vec(..) => "viper.vec_free",
+            _ => "free",
+        };
+        self.generate_node(arg);
+        self.emit("    mov %rax, %rdi");
+        self.call_aligned(function);
+    }
+
+    // The value is kept on the stack, above the vec, while the runtime makes room for it at the
+    // end of the vec, which can move the elements, and is then copied to where the runtime says
+    // it goes.
+    fn generate_push(&mut self, args: Vec<Node>) {
+        let mut args = args.into_iter();
+        let (vec, value) = (args.next().unwrap(), args.next().unwrap());
+        let elem = match vec.ty().repr() {
+            Type::Vec(elem) => *elem,
+            _ => panic!("push to a non vec"),
+        };
+        let size = self.layouts.size(&elem);
+        self.generate_node(vec);
+        self.push("%rax");
+        self.generate_arg(value);
+        let above = pushed_eightbytes(&elem, &self.layouts);
+        self.emit(format!("    mov {}(%rsp), %rdi", above * 8).as_str());
+        self.emit(format!("    mov ${}, %rsi", size).as_str());
+        self.call_aligned("viper.vec_push");
+        self.emit("    mov %rax, %rdi");
+        if elem.is_aggregate() {
+            let count = layout::eightbytes(size);
+            self.emit("    mov %rsp, %rsi");
+            self.copy(size);
+            self.emit(format!("    add ${}, %rsp", count * 8).as_str());
+            self.depth -= count;
+        } else {
+            self.pop("%rax");
+            self.store(&elem, "(%rdi)".to_string());
+        }
+        self.emit("    add $8, %rsp");
+        self.depth -= 1;
+    }
+
+    // The runtime leaves the popped element where it was, which the next push can write over,
+    // so a struct or array is copied out to the call's slot straight away.
+    fn generate_pop(&mut self, token: &Token, args: Vec<Node>, ty: &Type, offset: i64) {
+        let vec = args.into_iter().next().expect("pop without an argument");
+        let location = self.intern(format!(
+            "{}:{}:{}",
+            token.filepath, token.coord.0, token.coord.1
+        ));
+        self.generate_node(vec);
+        self.emit("    mov %rax, %rdi");
+        self.emit(format!("    mov ${}, %rsi", self.layouts.size(ty)).as_str());
+        self.emit(format!("    lea {}(%rip), %rdx", location).as_str());
+        self.call_aligned("viper.vec_pop");
+        if ty.is_aggregate() {
+            self.emit("    mov %rax, %rsi");
+            self.emit(format!("    lea -{}(%rbp), %rdi", offset).as_str());
+            self.copy(self.layouts.size(ty));
+            self.emit(format!("    lea -{}(%rbp), %rax", offset).as_str());
+        } else {
+            self.load(ty, "(%rax)".to_string());
+        }
+    }
+
+    // printing is done with libc's `dprintf`, which writes straight to the file descriptor
+    // rather than buffering, using a format that suits the type of the argument
+    fn generate_print(&mut self, builtin: Builtin, args: Vec<Node>) {
+        let (fd, newline) = builtin.output();
+        let mut format = match args.first().map(|x| x.ty().repr()) {
+            Some(Type::Str | Type::Bool | Type::Char) => "%s",
+            Some(Type::F64) => "%.15g",
+            Some(ty) if ty.is_signed() => "%ld",
+            Some(..) => "%lu",
+            None => "",
+        }
+        .to_string();
+        if newline {
+            format.push('\n');
+        }
+
+        let mut floats = 0;
+        if let Some(arg) = args.into_iter().next() {
+            let ty = arg.ty();
+            self.generate_node(arg);
+            match ty.repr() {
+                Type::F64 => {
+                    floats = 1;
+                    self.emit("    movq %rax, %xmm0");
+                }
+                Type::Bool => {
+                    let (yes, no) = (
+                        self.intern("true".to_string()),
+                        self.intern("false".to_string()),
+                    );
+                    self.emit("    cmp $0, %rax");
+                    self.emit(format!("    lea {}(%rip), %rdx", no).as_str());
+                    self.emit(format!("    lea {}(%rip), %rcx", yes).as_str());
+                    self.emit("    cmovne %rcx, %rdx");
+                }
+                // the runtime turns it into UTF-8
+                Type::Char => {
+                    self.emit("    mov %rax, %rdi");
+                    self.call_aligned("viper.encode_char");
+                    self.emit("    mov %rax, %rdx");
+                }
+                _ => self.emit("    mov %rax, %rdx"),
+            }
+        }
+        let format = self.intern(format);
+        self.emit(format!("    lea {}(%rip), %rsi", format).as_str());
+        self.emit(format!("    mov ${}, %edi", fd).as_str());
+        // variadic functions want the number of vector registers used in %al
+        self.emit(format!("    mov ${}, %eax", floats).as_str());
+        self.call_aligned("dprintf");
+    }
+
+    // calls a function that takes no arguments on the stack, padding %rsp out to 16 bytes if
+    // there is an odd number of values pushed
+    fn call_aligned(&mut self, name: &str) {
+        if self.depth % 2 == 1 {
+            self.emit("    sub $8, %rsp");
+            self.emit(format!("    call {}", name).as_str());
+            self.emit("    add $8, %rsp");
+        } else {
+            self.emit(format!("    call {}", name).as_str());
         }
     }
 
@@ -44,25 +1225,71 @@ impl Generator {
 
     fn generate_primary_expression(&mut self, token: Token) {
         match token.kind {
-            TokenKind::INT(x) => self.emit(format!("    mov ${}, %rax", x).as_str()),
+            TokenKind::INT(x) => self.generate_immediate(x.value as i64),
+            TokenKind::FLOAT(x) => self.generate_immediate(x.to_bits() as i64),
+            TokenKind::STR(x) => {
+                let label = self.intern(x);
+                self.emit(format!("    lea {}(%rip), %rax", label).as_str());
+            }
+            TokenKind::CHAR(x) => self.emit(format!("    mov ${}, %rax", x as u32).as_str()),
+            TokenKind::KEYWORD(KeywordKind::TRUE) => self.emit("    mov $1, %rax"),
+            TokenKind::KEYWORD(KeywordKind::FALSE | KeywordKind::NULL) => {
+                self.emit("    mov $0, %rax")
+            }
             TokenKind::COMMENT(x) => self.emit(format!("    #{}", x).as_str()),
             _ => panic!("unimplemented primary expression"),
         }
     }
 
+    // immediates that don't fit in 32 bits need the long form of mov
+    fn generate_immediate(&mut self, value: i64) {
+        if value == value as i32 as i64 {
+            self.emit(format!("    mov ${}, %rax", value).as_str());
+        } else {
+            self.emit(format!("    movabs ${}, %rax", value).as_str());
+        }
+    }
+
     fn generate_unary_expression(&mut self, node: Node) {
+        let ty = node.ty();
         match node {
+            Node::UnaryExpr { token, node } if token.kind == TokenKind::BIT_AND => {
+                self.generate_address(*node)
+            }
+            Node::UnaryExpr { ref token, .. } if token.kind == TokenKind::MUL => {
+                self.generate_address(node);
+                self.load(&ty, "(%rax)".to_string());
+            }
             Node::UnaryExpr { token, node } => {
                 self.generate_node(*node);
 
                 match token.kind {
-                    TokenKind::KEYWORD(KeywordKind::RETURN) => self.emit("    ret"),
+                    TokenKind::KEYWORD(KeywordKind::RETURN) => {
+                        if self.ret_ty.is_aggregate() {
+                            self.return_aggregate();
+                        }
+                        self.normalise(&self.ret_ty.clone());
+                        if self.ret_ty.is_float() {
+                            self.emit("    movq %rax, %xmm0");
+                        }
+                        self.emit(format!("    jmp .L.return.{}", self.function).as_str())
+                    }
+                    // a float is negated by flipping its sign bit
+                    TokenKind::MINUS if ty.is_float() => self.emit("    btc $63, %rax"),
                     TokenKind::MINUS => {
-                        self.emit("    mov %rax, %rbx");
-                        self.emit("    mov $0, %rax");
-                        self.emit("    sub %rbx, %rax");
+                        self.emit("    neg %rax");
+                        self.normalise(&ty);
+                    }
+                    TokenKind::BIT_NOT => {
+                        self.emit("    not %rax");
+                        self.normalise(&ty);
                     }
-                    TokenKind::PLUS => return,
+                    TokenKind::NOT => {
+                        self.emit("    cmp $0, %rax");
+                        self.emit("    sete %al");
+                        self.emit("    movzb %al, %rax");
+                    }
+                    TokenKind::PLUS => (),
                     _ => panic!("unimplemented unary operator"),
                 }
             }
@@ -73,39 +1300,382 @@ impl Generator {
     fn generate_binary_expression(&mut self, node: Node) {
         match node {
             Node::BinaryExpr { token, left, right } => match token.kind {
-                TokenKind::PLUS | TokenKind::MINUS | TokenKind::MUL | TokenKind::DIV => {
-                    self.generate_maths_expr(token, *left, *right)
-                }
-                TokenKind::EQUALS => self.generate_declaration(token, *left, *right),
+                TokenKind::PLUS
+                | TokenKind::MINUS
+                | TokenKind::MUL
+                | TokenKind::DIV
+                | TokenKind::MOD
+                | TokenKind::BIT_AND
+                | TokenKind::BIT_OR
+                | TokenKind::BIT_XOR
+                | TokenKind::SHIFT_LEFT
+                | TokenKind::SHIFT_RIGHT
+                | TokenKind::UNSIGNED_SHIFT_RIGHT => self.generate_maths_expr(token, *left, *right),
+                TokenKind::DOUBLE_EQUALS
+                | TokenKind::NOT_EQUALS
+                | TokenKind::LESS_THAN
+                | TokenKind::LESS_EQUALS
+                | TokenKind::GREATER_THAN
+                | TokenKind::GREATER_EQUALS => self.generate_comparison(token, *left, *right),
+                TokenKind::AND | TokenKind::OR => self.generate_logical_expr(token, *left, *right),
+                TokenKind::EQUALS => self.generate_assignment(*left, *right),
                 _ => panic!("unimplemented binary operator"),
             },
             _ => panic!("non binary node passed to generate_binary_expression"),
         }
     }
 
+    // Values are kept in the full 64 bits of %rax, sign or zero extended from their type's
+    // width, so everything can be worked out in 64 bits and only needs cutting back down to
+    // the type's width afterwards.
     fn generate_maths_expr(&mut self, token: Token, left: Node, right: Node) {
+        let ty = Node::operand_type(&left, &right);
+        let difference = right.ty().is_pointer();
         self.generate_node(right);
-        self.emit("    push %rax");
+        self.push("%rax");
         self.generate_node(left);
-        self.emit("    pop %rdi");
+        self.pop("%rdi");
+        if ty.is_float() {
+            return self.generate_float_maths(token);
+        }
+        if let Type::Pointer(elem) = ty.repr() {
+            return self.generate_pointer_maths(token, self.layouts.size(&elem), difference);
+        }
 
         match token.kind {
             TokenKind::PLUS => self.emit("    add %rdi, %rax"),
             TokenKind::MINUS => self.emit("    sub %rdi, %rax"),
             TokenKind::MUL => self.emit("    imul %rdi, %rax"),
-            TokenKind::DIV => {
-                self.emit("    cdq");
-                self.emit("    idiv %rdi");
+            TokenKind::DIV | TokenKind::MOD => {
+                if ty.is_signed() {
+                    self.emit("    cqo");
+                    self.emit("    idiv %rdi");
+                } else {
+                    self.emit("    xor %edx, %edx");
+                    self.emit("    div %rdi");
+                }
+                if token.kind == TokenKind::MOD {
+                    self.emit("    mov %rdx, %rax");
+                }
+            }
+            TokenKind::BIT_AND => self.emit("    and %rdi, %rax"),
+            TokenKind::BIT_OR => self.emit("    or %rdi, %rax"),
+            TokenKind::BIT_XOR => self.emit("    xor %rdi, %rax"),
+            // the shift amount has to be in %cl
+            TokenKind::SHIFT_LEFT => {
+                self.emit("    mov %rdi, %rcx");
+                self.emit("    shl %cl, %rax");
+            }
+            TokenKind::SHIFT_RIGHT => {
+                self.emit("    mov %rdi, %rcx");
+                if ty.is_signed() {
+                    self.emit("    sar %cl, %rax");
+                } else {
+                    self.emit("    shr %cl, %rax");
+                }
+            }
+            // a negative narrow value has to lose its sign extension before shifting, or the
+            // extended bits would get shifted in
+            TokenKind::UNSIGNED_SHIFT_RIGHT => {
+                self.zero_extend(&ty);
+                self.emit("    mov %rdi, %rcx");
+                self.emit("    shr %cl, %rax");
             }
             _ => panic!("unimplemented binary operator"),
         }
+        self.normalise(&ty);
+    }
+
+    // A pointer moves by whole elements of `size` bytes, so the integer added to or taken from
+    // it is scaled up to bytes first, and the bytes between two pointers are divided back down
+    // to elements. The left side is in %rax and the right in %rdi.
+    fn generate_pointer_maths(&mut self, token: Token, size: i64, difference: bool) {
+        if difference {
+            self.emit("    sub %rdi, %rax");
+            self.emit(format!("    mov ${}, %rdi", size).as_str());
+            self.emit("    cqo");
+            self.emit("    idiv %rdi");
+            return;
+        }
+        self.emit(format!("    imul ${}, %rdi", size).as_str());
+        match token.kind {
+            TokenKind::PLUS => self.emit("    add %rdi, %rax"),
+            TokenKind::MINUS => self.emit("    sub %rdi, %rax"),
+            _ => panic!("unimplemented pointer operator"),
+        }
+    }
+
+    // the left side is in %rax and the right in %rdi
+    fn generate_float_maths(&mut self, token: Token) {
+        self.emit("    movq %rax, %xmm0");
+        self.emit("    movq %rdi, %xmm1");
+        match token.kind {
+            TokenKind::PLUS => self.emit("    addsd %xmm1, %xmm0"),
+            TokenKind::MINUS => self.emit("    subsd %xmm1, %xmm0"),
+            TokenKind::MUL => self.emit("    mulsd %xmm1, %xmm0"),
+            TokenKind::DIV => self.emit("    divsd %xmm1, %xmm0"),
+            // there's no instruction for the remainder, so it's left to the runtime
+            TokenKind::MOD => self.call_aligned("viper.fmod"),
+            _ => panic!("unimplemented float operator"),
+        }
+        self.emit("    movq %xmm0, %rax");
+    }
+
+    // comparisons leave 1 in %rax if they hold and 0 if they don't
+    fn generate_comparison(&mut self, token: Token, left: Node, right: Node) {
+        let ty = Node::operand_type(&left, &right);
+        let signed = ty.is_signed();
+        self.generate_node(right);
+        self.push("%rax");
+        self.generate_node(left);
+        self.pop("%rdi");
+        if ty.is_float() {
+            return self.generate_float_comparison(token);
+        }
+
+        let set = match (token.kind, signed) {
+            (TokenKind::DOUBLE_EQUALS, _) => "sete",
+            (TokenKind::NOT_EQUALS, _) => "setne",
+            (TokenKind::LESS_THAN, true) => "setl",
+            (TokenKind::LESS_EQUALS, true) => "setle",
+            (TokenKind::GREATER_THAN, true) => "setg",
+            (TokenKind::GREATER_EQUALS, true) => "setge",
+            (TokenKind::LESS_THAN, false) => "setb",
+            (TokenKind::LESS_EQUALS, false) => "setbe",
+            (TokenKind::GREATER_THAN, false) => "seta",
+            (TokenKind::GREATER_EQUALS, false) => "setae",
+            _ => panic!("unimplemented comparison operator"),
+        };
+        self.emit("    cmp %rdi, %rax");
+        self.emit(format!("    {} %al", set).as_str());
+        self.emit("    movzb %al, %rax");
+    }
+
+    // `ucomisd` sets the flags like an unsigned comparison, except that comparing with a NaN
+    // sets all of ZF, PF and CF. `<` and `<=` are done as `>` and `>=` the other way round so
+    // that those come out false, and `==` and `!=` have to check PF.
+    fn generate_float_comparison(&mut self, token: Token) {
+        self.emit("    movq %rax, %xmm0");
+        self.emit("    movq %rdi, %xmm1");
+        match token.kind {
+            TokenKind::DOUBLE_EQUALS | TokenKind::NOT_EQUALS => {
+                let (set, parity, combine) = match token.kind {
+                    TokenKind::DOUBLE_EQUALS => ("sete", "setnp", "and"),
+                    _ => ("setne", "setp", "or"),
+                };
+                self.emit("    ucomisd %xmm1, %xmm0");
+                self.emit(format!("    {} %al", set).as_str());
+                self.emit(format!("    {} %dl", parity).as_str());
+                self.emit(format!("    {} %dl, %al", combine).as_str());
+            }
+            _ => {
+                let (operands, set) = match token.kind {
+                    TokenKind::LESS_THAN => ("%xmm0, %xmm1", "seta"),
+                    TokenKind::LESS_EQUALS => ("%xmm0, %xmm1", "setae"),
+                    TokenKind::GREATER_THAN => ("%xmm1, %xmm0", "seta"),
+                    TokenKind::GREATER_EQUALS => ("%xmm1, %xmm0", "setae"),
+                    _ => panic!("unimplemented comparison operator"),
+                };
+                self.emit(format!("    ucomisd {}", operands).as_str());
+                self.emit(format!("    {} %al", set).as_str());
+            }
+        }
+        self.emit("    movzb %al, %rax");
     }
 
-    // TODO: A large portion of the generator and parser need to be refactored for greater
-    // expandability, such as proper typing of declarations
-    fn generate_declaration(&mut self, token: Token, left: Node, right: Node) {
+    // `&&` and `||` only evaluate their right side if the left one didn't already decide the
+    // result
+    fn generate_logical_expr(&mut self, token: Token, left: Node, right: Node) {
+        let id = self.new_label();
+        let (jump, short_circuit) = match token.kind {
+            TokenKind::AND => ("je", 0),
+            TokenKind::OR => ("jne", 1),
+            _ => panic!("unimplemented logical operator"),
+        };
+
+        self.generate_node(left);
+        self.emit("    cmp $0, %rax");
+        self.emit(format!("    {} .L.short.{}", jump, id).as_str());
+        self.generate_node(right);
+        self.emit("    cmp $0, %rax");
+        self.emit(format!("    {} .L.short.{}", jump, id).as_str());
+        self.emit(format!("    mov ${}, %rax", 1 - short_circuit).as_str());
+        self.emit(format!("    jmp .L.end.{}", id).as_str());
+        self.emit(format!(".L.short.{}:", id).as_str());
+        self.emit(format!("    mov ${}, %rax", short_circuit).as_str());
+        self.emit(format!(".L.end.{}:", id).as_str());
+    }
+
+    fn generate_assignment(&mut self, left: Node, right: Node) {
+        let ty = left.ty();
+        if let Node::Variable { .. } = left {
+            let address = address_of(&left);
+            self.generate_node(right);
+            self.normalise(&ty);
+            return self.store(&ty, address);
+        }
+
         self.generate_node(right);
-        self.emit("    lea -4(%rbp), %rax");
+        self.normalise(&ty);
+        self.push("%rax");
+        self.generate_address(left);
+        self.emit("    mov %rax, %rdi");
+        self.pop("%rax");
+        self.store(&ty, "(%rdi)".to_string());
+    }
+
+    fn store_local(&mut self, offset: i64, ty: &Type) {
+        self.store(ty, format!("-{}(%rbp)", offset));
+    }
+
+    // stores the bottom bytes of %rax, as many as a `ty` takes up, or for a struct or array,
+    // copies the one whose address is in %rax
+    fn store(&mut self, ty: &Type, addr: String) {
+        if ty.is_aggregate() {
+            self.emit(format!("    lea {}, %rdi", addr).as_str());
+            self.emit("    mov %rax, %rsi");
+            self.copy(self.layouts.size(ty));
+        } else if self.layouts.size(ty) > 0 {
+            let reg = sized_register("%rax", self.layouts.size(ty));
+            self.emit(format!("    mov {}, {}", reg, addr).as_str());
+        }
+    }
+
+    // copies `size` bytes from where %rsi points to where %rdi points
+    fn copy(&mut self, size: i64) {
+        self.emit(format!("    mov ${}, %rcx", size).as_str());
+        self.emit("    rep movsb");
+    }
+
+    // Loads the `size` bytes at `offset(base)` into %rax, zero extended, without reading past
+    // them. A size that isn't a power of two is put together from two loads that overlap.
+    fn load_bytes(&mut self, size: i64, offset: i64, base: &str) {
+        let load = |size: i64| match size {
+            1 => "movzbl",
+            2 => "movzwl",
+            4 => "movl",
+            _ => "mov",
+        };
+        if size.count_ones() == 1 {
+            let reg = if size == 8 { "%rax" } else { "%eax" };
+            self.emit(format!("    {} {}({}), {}", load(size), offset, base, reg).as_str());
+            return;
+        }
+        let low = 1 << (63 - size.leading_zeros());
+        let high = size - low;
+        self.emit(format!("    {} {}({}), %eax", load(low), offset + high, base).as_str());
+        self.emit(format!("    shl ${}, %rax", high * 8).as_str());
+        self.emit(format!("    {} {}({}), %ecx", load(low), offset, base).as_str());
+        self.emit("    or %rcx, %rax");
+    }
+
+    // Stores the bottom `size` bytes of `reg` at `offset(base)`, split up the same way as
+    // `load_bytes`, which can shift what is left in `reg` along.
+    fn store_bytes(&mut self, reg: &str, size: i64, offset: i64, base: &str) {
+        if size.count_ones() == 1 {
+            let part = sized_register(reg, size);
+            self.emit(format!("    mov {}, {}({})", part, offset, base).as_str());
+            return;
+        }
+        let low = 1 << (63 - size.leading_zeros());
+        let high = size - low;
+        let part = sized_register(reg, low);
+        self.emit(format!("    mov {}, {}({})", part, offset, base).as_str());
+        self.emit(format!("    shr ${}, {}", high * 8, reg).as_str());
+        self.emit(format!("    mov {}, {}({})", part, offset + high, base).as_str());
+    }
+
+    // loads a value of type `ty` into %rax, extended to the full 64 bits. Structs and arrays
+    // don't fit, so their address is used instead.
+    fn load(&mut self, ty: &Type, addr: String) {
+        match ty.repr() {
+            Type::I8 => self.emit(format!("    movsbq {}, %rax", addr).as_str()),
+            Type::U8 | Type::Bool => self.emit(format!("    movzbq {}, %rax", addr).as_str()),
+            Type::I16 => self.emit(format!("    movswq {}, %rax", addr).as_str()),
+            Type::U16 => self.emit(format!("    movzwq {}, %rax", addr).as_str()),
+            Type::I32 => self.emit(format!("    movslq {}, %rax", addr).as_str()),
+            // writing to a 32 bit register clears the top half
+            Type::U32 | Type::Char => self.emit(format!("    movl {}, %eax", addr).as_str()),
+            Type::I64 | Type::U64 | Type::F64 | Type::Pointer(..) | Type::Vec(..) | Type::Str => {
+                self.emit(format!("    mov {}, %rax", addr).as_str())
+            }
+            Type::Array(..) | Type::Struct(..) | Type::Enum(..) => {
+                self.emit(format!("    lea {}, %rax", addr).as_str())
+            }
+            Type::Unit => self.emit("    mov $0, %rax"),
+            Type::Alias(..) | Type::Newtype(..) => panic!("type left unexpanded"),
+            Type::Infer(..) => panic!("type left for inference after type checking"),
+        }
+    }
+
+    // turns the value in %rax from one number type into the other, leaving casts between
+    // integers to `normalise`. Floats are truncated towards zero, and ones that don't fit in
+    // an i64 come out as i64::MIN.
+    fn convert(&mut self, from: &Type, to: &Type) {
+        match (from.is_float(), to.is_float()) {
+            (true, false) => {
+                self.emit("    movq %rax, %xmm0");
+                self.emit("    cvttsd2si %xmm0, %rax");
+            }
+            // `cvtsi2sd` only takes signed values, so a u64 with its top bit set is halved
+            // first, keeping the bottom bit so it still rounds the same way, and doubled after
+            (false, true) if from.repr() == Type::U64 => {
+                let id = self.new_label();
+                self.emit("    test %rax, %rax");
+                self.emit(format!("    js .L.else.{}", id).as_str());
+                self.emit("    cvtsi2sdq %rax, %xmm0");
+                self.emit(format!("    jmp .L.end.{}", id).as_str());
+                self.emit(format!(".L.else.{}:", id).as_str());
+                self.emit("    mov %rax, %rdi");
+                self.emit("    shr %rdi");
+                self.emit("    and $1, %eax");
+                self.emit("    or %rax, %rdi");
+                self.emit("    cvtsi2sdq %rdi, %xmm0");
+                self.emit("    addsd %xmm0, %xmm0");
+                self.emit(format!(".L.end.{}:", id).as_str());
+                self.emit("    movq %xmm0, %rax");
+            }
+            (false, true) => {
+                self.emit("    cvtsi2sdq %rax, %xmm0");
+                self.emit("    movq %xmm0, %rax");
+            }
+            _ => (),
+        }
+    }
+
+    // cuts %rax down to the width of `ty` and extends it back out to 64 bits
+    fn normalise(&mut self, ty: &Type) {
+        match ty.repr() {
+            Type::I8 => self.emit("    movsbq %al, %rax"),
+            Type::I16 => self.emit("    movswq %ax, %rax"),
+            Type::I32 => self.emit("    movslq %eax, %rax"),
+            _ if ty.is_aggregate() => (),
+            _ => self.zero_extend(ty),
+        }
+    }
+
+    fn zero_extend(&mut self, ty: &Type) {
+        match self.layouts.size(ty) {
+            1 => self.emit("    movzbq %al, %rax"),
+            2 => self.emit("    movzwq %ax, %rax"),
+            4 => self.emit("    movl %eax, %eax"),
+            _ => (),
+        }
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.label_count += 1;
+        self.label_count
+    }
+
+    fn push(&mut self, reg: &str) {
+        self.emit(format!("    push {}", reg).as_str());
+        self.depth += 1;
+    }
+
+    fn pop(&mut self, reg: &str) {
+        self.emit(format!("    pop {}", reg).as_str());
+        self.depth -= 1;
     }
 
     // TODO: replace expect with "?"
@@ -0,0 +1,347 @@
+use crate::types::{Type, MAX_SIZE};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// a field of a struct, and how many bytes from the start of the struct it is
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub ty: Type,
+    pub offset: i64,
+}
+
+// Where each field of a struct goes, the same as a C compiler would lay out a struct with the
+// same fields in the same order. Every field starts at the next multiple of its alignment, and
+// the struct is as aligned as its most aligned field, padded at the end to a multiple of that
+// so each element of an array of them is aligned too.
+#[derive(Debug)]
+pub struct Layout {
+    pub fields: Vec<Field>,
+    pub size: i64,
+    pub align: i64,
+}
+
+impl Layout {
+    // the fields have to be of types that are already in `layouts`, so their sizes are known
+    pub fn new(fields: Vec<(String, Type)>, layouts: &Layouts) -> Self {
+        let mut offset = 0;
+        let mut align = 1;
+        let fields = fields
+            .into_iter()
+            .map(|(name, ty)| {
+                offset = align_to(offset, layouts.align(&ty));
+                align = align.max(layouts.align(&ty));
+                let field = Field { name, offset, ty };
+                offset += layouts.size(&field.ty);
+                field
+            })
+            .collect();
+        Self {
+            fields,
+            size: align_to(offset, align),
+            align,
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|x| x.name == name)
+    }
+}
+
+// the type of the tag at the start of every enum
+pub const TAG: Type = Type::U32;
+
+// A variant of an enum, whose fields are named after their position. The offsets are from the
+// start of the whole enum rather than from the start of the payload.
+#[derive(Debug)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+// An enum is a `u32` tag saying which variant it holds, followed by the fields of that variant.
+// Each variant is laid out like a struct of its own and they all start at the same place, so
+// it's the same as a C struct of the tag and a union of those structs.
+#[derive(Debug)]
+pub struct EnumLayout {
+    pub variants: Vec<Variant>,
+    pub size: i64,
+    pub align: i64,
+}
+
+impl EnumLayout {
+    pub fn new(variants: Vec<(String, Vec<Type>)>, layouts: &Layouts) -> Self {
+        let payloads: Vec<(String, Layout)> = variants
+            .into_iter()
+            .map(|(name, fields)| {
+                let fields = fields
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, ty)| (i.to_string(), ty))
+                    .collect();
+                (name, Layout::new(fields, layouts))
+            })
+            .collect();
+        let payload_align = payloads.iter().map(|(_, x)| x.align).max().unwrap_or(1);
+        let payload_size = payloads.iter().map(|(_, x)| x.size).max().unwrap_or(0);
+        let start = align_to(TAG.scalar_size(), payload_align);
+        let align = payload_align.max(TAG.scalar_size());
+        let variants = payloads
+            .into_iter()
+            .map(|(name, payload)| Variant {
+                name,
+                fields: payload
+                    .fields
+                    .into_iter()
+                    .map(|x| Field {
+                        offset: x.offset + start,
+                        ..x
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            variants,
+            size: align_to(start + payload_size, align),
+            align,
+        }
+    }
+
+    // the variant called `name`, along with its tag
+    pub fn variant(&self, name: &str) -> Option<(usize, &Variant)> {
+        self.variants
+            .iter()
+            .enumerate()
+            .find(|(_, x)| x.name == name)
+    }
+}
+
+pub fn align_to(offset: i64, align: i64) -> i64 {
+    (offset + align - 1) / align * align
+}
+
+// The layout of every struct and enum, by name. `Type::Struct` and `Type::Enum` only hold the
+// name, so anything that needs to know their size or fields looks them up here. The semantic
+// pass declares each one in it, then hands it on to the passes after it.
+#[derive(Debug, Clone, Default)]
+pub struct Layouts {
+    structs: HashMap<String, Rc<Layout>>,
+    enums: HashMap<String, Rc<EnumLayout>>,
+}
+
+impl Layouts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the fields have to be of types that are already declared, so their sizes are known
+    pub fn declare(&mut self, name: &str, fields: Vec<(String, Type)>) {
+        let layout = Rc::new(Layout::new(fields, self));
+        self.structs.insert(name.to_string(), layout);
+    }
+
+    pub fn declare_enum(&mut self, name: &str, variants: Vec<(String, Vec<Type>)>) {
+        let layout = Rc::new(EnumLayout::new(variants, self));
+        self.enums.insert(name.to_string(), layout);
+    }
+
+    pub fn of(&self, name: &str) -> Rc<Layout> {
+        self.structs
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| panic!("struct {} used before it was declared", name))
+    }
+
+    pub fn of_enum(&self, name: &str) -> Rc<EnumLayout> {
+        self.enums
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| panic!("enum {} used before it was declared", name))
+    }
+
+    // size in bytes, which the semantic pass has made sure is at most `MAX_SIZE`
+    pub fn size(&self, ty: &Type) -> i64 {
+        self.checked_size(ty)
+            .unwrap_or_else(|| panic!("size of {} is more than MAX_SIZE", ty))
+    }
+
+    // size in bytes, or `None` if it is more than `MAX_SIZE`
+    pub fn checked_size(&self, ty: &Type) -> Option<i64> {
+        let size = match ty.repr() {
+            Type::Array(elem, len) => self.checked_size(&elem)?.checked_mul(len as i64)?,
+            Type::Struct(name) => self.of(&name).size,
+            Type::Enum(name) => self.of_enum(&name).size,
+            ty => ty.scalar_size(),
+        };
+        Some(size).filter(|x| *x <= MAX_SIZE)
+    }
+
+    // what its address has to be a multiple of, which for anything but an aggregate is its size
+    pub fn align(&self, ty: &Type) -> i64 {
+        match ty.repr() {
+            Type::Array(elem, ..) => self.align(&elem),
+            Type::Struct(name) => self.of(&name).align,
+            Type::Enum(name) => self.of_enum(&name).align,
+            ty => ty.scalar_size().max(1),
+        }
+    }
+
+    // How the System V ABI passes a value of type `ty`, as the class of each of its
+    // eightbytes. An eightbyte is SSE if everything in it is a float and INTEGER otherwise.
+    // Anything bigger than two eightbytes is passed in memory instead, which is `None`.
+    pub fn classify(&self, ty: &Type) -> Option<Vec<Class>> {
+        let size = self.size(ty);
+        if size > 16 {
+            return None;
+        }
+        let mut classes = vec![Class::Sse; eightbytes(size)];
+        let mut scalars = Vec::new();
+        self.flatten(ty, 0, &mut scalars);
+        for (offset, ty) in scalars {
+            if !ty.is_float() {
+                classes[offset as usize / 8] = Class::Integer;
+            }
+        }
+        Some(classes)
+    }
+
+    // every value that isn't an aggregate inside `ty`, with its offset. The variants of an enum
+    // overlap, so all of their fields are in there, the same as for a C union.
+    fn flatten(&self, ty: &Type, offset: i64, scalars: &mut Vec<(i64, Type)>) {
+        match ty.repr() {
+            Type::Array(elem, len) => {
+                for i in 0..len as i64 {
+                    self.flatten(&elem, offset + i * self.size(&elem), scalars);
+                }
+            }
+            Type::Struct(name) => {
+                for field in self.of(&name).fields.iter() {
+                    self.flatten(&field.ty, offset + field.offset, scalars);
+                }
+            }
+            Type::Enum(name) => {
+                scalars.push((offset, TAG));
+                for variant in self.of_enum(&name).variants.iter() {
+                    for field in variant.fields.iter() {
+                        self.flatten(&field.ty, offset + field.offset, scalars);
+                    }
+                }
+            }
+            ty => scalars.push((offset, ty)),
+        }
+    }
+}
+
+// which kind of register an eightbyte of a value goes in when it is passed or returned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Integer,
+    Sse,
+}
+
+// how many eightbytes it takes to hold `size` bytes
+pub fn eightbytes(size: i64) -> usize {
+    (size as usize).div_ceil(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::check;
+
+    fn declared(source: &str) -> Layouts {
+        check(source).unwrap_or_else(|x| panic!("{}", x)).1
+    }
+
+    fn classes(source: &str, ty: Type) -> Option<Vec<Class>> {
+        declared(source).classify(&ty)
+    }
+
+    fn offsets(fields: &[Field]) -> Vec<i64> {
+        fields.iter().map(|x| x.offset).collect()
+    }
+
+    #[test]
+    fn lays_out_structs_like_c() {
+        let layouts =
+            declared("struct S { a: u8, b: i64, c: u16 }\nstruct T { a: u8, s: S, b: [u16; 3] }");
+        let s = layouts.of("S");
+        assert_eq!(
+            (offsets(&s.fields), s.size, s.align),
+            (vec![0, 8, 16], 24, 8)
+        );
+        let t = layouts.of("T");
+        assert_eq!(
+            (offsets(&t.fields), t.size, t.align),
+            (vec![0, 8, 32], 40, 8)
+        );
+        let layouts = declared("struct E {}\nstruct P { a: u8, b: [u8; 2] }");
+        assert_eq!((layouts.of("E").size, layouts.of("E").align), (0, 1));
+        assert_eq!((layouts.of("P").size, layouts.of("P").align), (3, 1));
+    }
+
+    #[test]
+    fn lays_out_enums_as_a_tag_and_a_union() {
+        let layouts = declared("enum E { A(u8), B(i64, u16), C }\nenum Small { A(u8, u8), B }");
+        let e = layouts.of_enum("E");
+        assert_eq!((e.size, e.align), (24, 8));
+        let (tag, b) = e.variant("B").unwrap();
+        assert_eq!((tag, offsets(&b.fields)), (1, vec![8, 16]));
+        let small = layouts.of_enum("Small");
+        assert_eq!((small.size, small.align), (8, 4));
+        assert_eq!(offsets(&small.variant("A").unwrap().1.fields), vec![4, 5]);
+    }
+
+    #[test]
+    fn classifies_eightbytes_like_gcc() {
+        use Class::*;
+        let source = "struct S { a: u8, b: f64 }";
+        assert_eq!(
+            classes(source, Type::Struct("S".into())),
+            Some(vec![Integer, Sse])
+        );
+        let source = "struct S { a: f64, b: f64 }";
+        assert_eq!(
+            classes(source, Type::Struct("S".into())),
+            Some(vec![Sse, Sse])
+        );
+        // an eightbyte with an integer anywhere in it is INTEGER
+        let source = "struct S { a: i32, b: [u8; 3], c: f64 }";
+        assert_eq!(
+            classes(source, Type::Struct("S".into())),
+            Some(vec![Integer, Sse])
+        );
+        let source = "struct In { x: f64 }\nstruct S { a: In, b: i64 }";
+        assert_eq!(
+            classes(source, Type::Struct("S".into())),
+            Some(vec![Sse, Integer])
+        );
+        let ty = Type::Array(Box::new(Type::I32), 3);
+        assert_eq!(classes("", ty), Some(vec![Integer, Integer]));
+        let ty = Type::Array(Box::new(Type::F64), 2);
+        assert_eq!(classes("", ty), Some(vec![Sse, Sse]));
+    }
+
+    #[test]
+    fn classifies_overlapping_variants_together() {
+        use Class::*;
+        let source = "enum E { A(f64), B }";
+        assert_eq!(
+            classes(source, Type::Enum("E".into())),
+            Some(vec![Integer, Sse])
+        );
+        let source = "enum E { A(f64), B(i64) }";
+        assert_eq!(
+            classes(source, Type::Enum("E".into())),
+            Some(vec![Integer, Integer])
+        );
+    }
+
+    #[test]
+    fn passes_anything_over_two_eightbytes_in_memory() {
+        let source = "struct S { a: f64, b: f64, c: f64 }";
+        assert_eq!(classes(source, Type::Struct("S".into())), None);
+        let source = "struct S { a: u8, b: [u8; 16] }";
+        assert_eq!(classes(source, Type::Struct("S".into())), None);
+    }
+}
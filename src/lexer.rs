@@ -1,4 +1,5 @@
 use crate::error;
+use crate::types::Type;
 use std::fmt;
 
 // =========================
@@ -8,11 +9,58 @@ use std::fmt;
 #[derive(Debug, Clone, PartialEq)]
 pub enum KeywordKind {
     RETURN,
+    LET,
+    FN,
+    IF,
+    ELSE,
+    WHILE,
+    FOR,
+    IN,
+    LOOP,
+    BREAK,
+    CONTINUE,
+    AS,
+    MUT,
+    CONST,
+    STATIC,
+    STRUCT,
+    ENUM,
+    MATCH,
+    TYPE,
+    NEWTYPE,
+    NULL,
+    TRUE,
+    FALSE,
+}
+
+// `ty` is the type given by a suffix such as `255u8`, without one the literal is an i64
+#[derive(Clone, PartialEq)]
+pub struct IntLiteral {
+    pub value: u64,
+    pub ty: Option<Type>,
+}
+
+impl IntLiteral {
+    pub fn ty(&self) -> Type {
+        self.ty.clone().unwrap_or(Type::I64)
+    }
+}
+
+impl fmt::Debug for IntLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ty {
+            Some(ty) => write!(f, "{}{}", self.value, ty),
+            None => write!(f, "{}", self.value),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
-    INT(i32),
+    INT(IntLiteral),
+    FLOAT(f64),
+    STR(String),
+    CHAR(char),
     DECL_NAME(String),
     KEYWORD(KeywordKind),
     COMMENT(String),
@@ -20,12 +68,38 @@ pub enum TokenKind {
     MINUS,
     MUL,
     DIV,
+    MOD,
+    BIT_AND,
+    BIT_OR,
+    BIT_XOR,
+    BIT_NOT,
+    SHIFT_LEFT,
+    SHIFT_RIGHT,
+    UNSIGNED_SHIFT_RIGHT,
     EQUALS,
+    DOUBLE_EQUALS,
+    NOT_EQUALS,
+    LESS_THAN,
+    LESS_EQUALS,
+    GREATER_THAN,
+    GREATER_EQUALS,
+    AND,
+    OR,
+    NOT,
     SEMICOLON,
+    COLON,
+    DOUBLE_COLON,
+    COMMA,
+    DOT,
+    DOTDOT,
+    ARROW,
+    FAT_ARROW,
     LPAREN,
     RPAREN,
     CURLY_LPAREN,
     CURLY_RPAREN,
+    SQUARE_LPAREN,
+    SQUARE_RPAREN,
     EOF,
 }
 
@@ -40,9 +114,9 @@ pub struct Token {
 impl Token {
     pub fn new(kind: TokenKind, filepath: String, coord: (u32, u32)) -> Self {
         Self {
-            kind: kind,
-            filepath: filepath,
-            coord: coord,
+            kind,
+            filepath,
+            coord,
         }
     }
 }
@@ -57,8 +131,6 @@ impl fmt::Display for Token {
 //  Lexer
 // =========================
 
-const WHITESPACE: [char; 4] = [' ', '\t', '\n', '\0'];
-
 pub struct Lexer {
     input: String,
     current_char: char,
@@ -73,12 +145,12 @@ pub struct Lexer {
 impl Lexer {
     pub fn new(input: String, filepath: String) -> Self {
         Self {
-            input: input,
+            input,
             current_char: '\0',
             pos: 0,
             tokens: Vec::new(),
 
-            filepath: filepath,
+            filepath,
             coord: (1, 1),
         }
     }
@@ -90,22 +162,54 @@ impl Lexer {
             match self.current_char {
                 ' ' | '\t' | '\n' => self.advance(),
                 '0'..='9' => {
-                    let token = self.num_token();
+                    let token = self.num_token()?;
                     self.tokens.push(token);
                 }
-                'a'..='z' | 'A'..='Z' => {
+                'a'..='z' | 'A'..='Z' | '_' => {
                     self.check_keyword();
                 }
+                '"' => self.string_token()?,
+                '\'' => self.char_token()?,
                 '+' => self.push_token(TokenKind::PLUS),
+                '-' if self.peek_char() == '>' => self.push_long_token(TokenKind::ARROW, 2),
                 '-' => self.push_token(TokenKind::MINUS),
                 '*' => self.push_token(TokenKind::MUL),
                 '/' => self.comment(),
+                '%' => self.push_token(TokenKind::MOD),
+                '^' => self.push_token(TokenKind::BIT_XOR),
+                '~' => self.push_token(TokenKind::BIT_NOT),
+                '=' if self.peek_char() == '=' => self.push_long_token(TokenKind::DOUBLE_EQUALS, 2),
+                '=' if self.peek_char() == '>' => self.push_long_token(TokenKind::FAT_ARROW, 2),
                 '=' => self.push_token(TokenKind::EQUALS),
+                '!' if self.peek_char() == '=' => self.push_long_token(TokenKind::NOT_EQUALS, 2),
+                '!' => self.push_token(TokenKind::NOT),
+                '<' if self.peek_char() == '=' => self.push_long_token(TokenKind::LESS_EQUALS, 2),
+                '<' if self.peek_char() == '<' => self.push_long_token(TokenKind::SHIFT_LEFT, 2),
+                '<' => self.push_token(TokenKind::LESS_THAN),
+                '>' if self.peek_char() == '=' => {
+                    self.push_long_token(TokenKind::GREATER_EQUALS, 2)
+                }
+                '>' if self.next_chars_are(">>>") => {
+                    self.push_long_token(TokenKind::UNSIGNED_SHIFT_RIGHT, 3)
+                }
+                '>' if self.peek_char() == '>' => self.push_long_token(TokenKind::SHIFT_RIGHT, 2),
+                '>' => self.push_token(TokenKind::GREATER_THAN),
+                '&' if self.peek_char() == '&' => self.push_long_token(TokenKind::AND, 2),
+                '&' => self.push_token(TokenKind::BIT_AND),
+                '|' if self.peek_char() == '|' => self.push_long_token(TokenKind::OR, 2),
+                '|' => self.push_token(TokenKind::BIT_OR),
                 ';' => self.push_token(TokenKind::SEMICOLON),
+                ':' if self.peek_char() == ':' => self.push_long_token(TokenKind::DOUBLE_COLON, 2),
+                ':' => self.push_token(TokenKind::COLON),
+                ',' => self.push_token(TokenKind::COMMA),
+                '.' if self.peek_char() == '.' => self.push_long_token(TokenKind::DOTDOT, 2),
+                '.' => self.push_token(TokenKind::DOT),
                 '(' => self.push_token(TokenKind::LPAREN),
                 ')' => self.push_token(TokenKind::RPAREN),
                 '{' => self.push_token(TokenKind::CURLY_LPAREN),
                 '}' => self.push_token(TokenKind::CURLY_RPAREN),
+                '[' => self.push_token(TokenKind::SQUARE_LPAREN),
+                ']' => self.push_token(TokenKind::SQUARE_RPAREN),
                 '\0' => {
                     self.push_token(TokenKind::EOF);
                     return Ok(self.tokens.clone());
@@ -129,6 +233,18 @@ impl Lexer {
         }
     }
 
+    fn peek_char(&self) -> char {
+        if self.pos + 1 < self.input.len() {
+            self.input.as_bytes()[self.pos + 1] as char
+        } else {
+            '\0'
+        }
+    }
+
+    fn next_chars_are(&self, chars: &str) -> bool {
+        self.input[self.pos..].starts_with(chars)
+    }
+
     fn advance(&mut self) {
         self.pos += 1;
         self.coord.1 += 1;
@@ -145,55 +261,298 @@ impl Lexer {
         self.advance();
     }
 
-    fn num_token(&mut self) -> Token {
+    // for tokens made up of more than one character
+    fn push_long_token(&mut self, kind: TokenKind, len: usize) {
+        self.push_token_at(kind, self.coord);
+        for _ in 0..len {
+            self.advance();
+        }
+    }
+
+    // for tokens that have already been consumed, so the coord has to be remembered by the caller
+    fn push_token_at(&mut self, kind: TokenKind, coord: (u32, u32)) {
+        self.tokens
+            .push(Token::new(kind, self.filepath.clone(), coord));
+    }
+
+    // an integer literal is digits, optionally split up with `_`, followed by an optional
+    // type suffix. A fractional part or an exponent makes it a float instead, as does an
+    // `f64` suffix.
+    fn num_token(&mut self) -> Result<Token, error::Error> {
+        let coord = self.coord;
         let mut num_str = String::new();
+        let mut suffix = String::new();
+        let mut float = false;
 
-        while self.current_char.is_alphanumeric() {
-            num_str.push_str(&self.current_char.to_string());
+        self.digits(&mut num_str);
+        // `0..10` is a range rather than a float followed by `.10`
+        if self.current_char == '.' && self.peek_char().is_ascii_digit() {
+            float = true;
+            num_str.push('.');
             self.advance();
+            self.digits(&mut num_str);
         }
+        let sign = matches!(self.peek_char(), '+' | '-');
+        if matches!(self.current_char, 'e' | 'E')
+            && (self.peek_char().is_ascii_digit() || sign && self.is_digit_at(2))
+        {
+            float = true;
+            num_str.push('e');
+            self.advance();
+            if sign {
+                num_str.push(self.current_char);
+                self.advance();
+            }
+            self.digits(&mut num_str);
+        }
+        while self.current_char.is_ascii_alphanumeric() {
+            suffix.push(self.current_char);
+            self.advance();
+        }
+
+        if float || suffix == "f64" {
+            return match (suffix.as_str(), num_str.parse::<f64>()) {
+                ("" | "f64", Ok(x)) => Ok(Token::new(
+                    TokenKind::FLOAT(x),
+                    self.filepath.clone(),
+                    coord,
+                )),
+                _ => Err(error::Error::FoundExpectedError {
+                    found: format!("{}{}", num_str, suffix),
+                    expected: "float type suffix".to_string(),
+                    filepath: self.filepath.clone(),
+                    coord,
+                }),
+            };
+        }
+
+        let ty = match suffix.as_str() {
+            "" => None,
+            x => match Type::from_name(x) {
+                Some(ty) if ty.is_integer() => Some(ty),
+                _ => {
+                    return Err(error::Error::FoundExpectedError {
+                        found: format!("{}{}", num_str, suffix),
+                        expected: "integer type suffix".to_string(),
+                        filepath: self.filepath.clone(),
+                        coord,
+                    })
+                }
+            },
+        };
+        // the minus sign is a separate token, so `128i8` has to get through for `-128i8` to work
+        let (min, max) = ty.clone().unwrap_or(Type::U64).int_range();
+        let value = match num_str.parse::<u64>() {
+            Ok(x) if (x as i128) <= max.max(-min) => x,
+            _ => {
+                return Err(error::Error::FoundExpectedError {
+                    found: format!("{}{}", num_str, suffix),
+                    expected: format!("integer in range {}..={}", min, max),
+                    filepath: self.filepath.clone(),
+                    coord,
+                })
+            }
+        };
 
-        Token::new(
-            TokenKind::INT(num_str.parse().expect("Cannot parse non int")),
+        Ok(Token::new(
+            TokenKind::INT(IntLiteral { value, ty }),
             self.filepath.clone(),
-            self.coord,
-        )
+            coord,
+        ))
+    }
+
+    // digits, leaving out any `_` used to split them up
+    fn digits(&mut self, num_str: &mut String) {
+        while self.current_char.is_ascii_digit() || self.current_char == '_' {
+            if self.current_char != '_' {
+                num_str.push(self.current_char);
+            }
+            self.advance();
+        }
+    }
+
+    // whether the character `offset` places ahead is a digit
+    fn is_digit_at(&self, offset: usize) -> bool {
+        self.input
+            .as_bytes()
+            .get(self.pos + offset)
+            .is_some_and(|x| x.is_ascii_digit())
+    }
+
+    fn string_token(&mut self) -> Result<(), error::Error> {
+        let coord = self.coord;
+        self.advance();
+        // the input is worked through a byte at a time, so anything that isn't ASCII is put back
+        // together from its bytes at the end
+        let mut bytes = Vec::new();
+        while self.current_char != '"' {
+            match self.current_char {
+                '\0' => {
+                    return Err(error::Error::FoundExpectedError {
+                        found: "end of file".to_string(),
+                        expected: "`\"` to end the string".to_string(),
+                        filepath: self.filepath.clone(),
+                        coord,
+                    })
+                }
+                '\\' => {
+                    let c = self.escape(coord)?;
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                x => {
+                    bytes.push(x as u8);
+                    self.advance();
+                }
+            }
+        }
+        self.advance();
+
+        let text = String::from_utf8(bytes).expect("string literal is not valid UTF-8");
+        self.push_token_at(TokenKind::STR(text), coord);
+        Ok(())
+    }
+
+    fn char_token(&mut self) -> Result<(), error::Error> {
+        let coord = self.coord;
+        self.advance();
+        let c = match self.current_char {
+            '\\' => self.escape(coord)?,
+            '\'' | '\n' | '\0' => {
+                return Err(error::Error::FoundExpectedError {
+                    found: format!("{:?}", self.current_char),
+                    expected: "a character".to_string(),
+                    filepath: self.filepath.clone(),
+                    coord,
+                })
+            }
+            _ => {
+                let c = self.input[self.pos..].chars().next().unwrap();
+                for _ in 0..c.len_utf8() {
+                    self.advance();
+                }
+                c
+            }
+        };
+        if self.current_char != '\'' {
+            return Err(error::Error::FoundExpectedError {
+                found: format!("{:?}", self.current_char),
+                expected: "`'` to end the character".to_string(),
+                filepath: self.filepath.clone(),
+                coord,
+            });
+        }
+        self.advance();
+
+        self.push_token_at(TokenKind::CHAR(c), coord);
+        Ok(())
+    }
+
+    // works out the escape sequence starting at the current `\`. Errors point at `start`, the
+    // beginning of the literal the escape is in.
+    fn escape(&mut self, start: (u32, u32)) -> Result<char, error::Error> {
+        self.advance();
+        let c = self.current_char;
+        self.advance();
+        let escaped = match c {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '\'' => Some('\''),
+            // exactly two hex digits, and only as far as ASCII goes
+            'x' => {
+                let digits = self.input.get(self.pos..self.pos + 2).unwrap_or("");
+                let value = u8::from_str_radix(digits, 16).ok().filter(|x| *x <= 0x7f);
+                if value.is_some() {
+                    self.advance();
+                    self.advance();
+                }
+                value.map(char::from)
+            }
+            // one to six hex digits in braces, naming any unicode scalar value
+            'u' if self.current_char == '{' => {
+                self.advance();
+                let mut digits = String::new();
+                while self.current_char.is_ascii_hexdigit() && digits.len() < 6 {
+                    digits.push(self.current_char);
+                    self.advance();
+                }
+                if self.current_char == '}' {
+                    self.advance();
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        escaped.ok_or_else(|| error::Error::FoundExpectedError {
+            found: format!("`\\{}`", c),
+            expected: "escape sequence".to_string(),
+            filepath: self.filepath.clone(),
+            coord: start,
+        })
     }
 
     fn comment(&mut self) {
+        let coord = self.coord;
         self.advance();
-        if self.current_char.clone() == '/' {
+        if self.current_char == '/' {
             self.advance();
             let mut comment = String::new();
-            while self.current_char.clone() != '\n' && self.current_char.clone() != '\0' {
-                comment.push(self.current_char.clone());
+            while self.current_char != '\n' && self.current_char != '\0' {
+                comment.push(self.current_char);
                 self.advance();
             }
-            self.push_token(TokenKind::COMMENT(comment));
+            self.push_token_at(TokenKind::COMMENT(comment), coord);
         } else {
-            self.push_token(TokenKind::DIV);
+            self.push_token_at(TokenKind::DIV, coord);
         }
     }
 
     fn check_keyword(&mut self) {
+        let coord = self.coord;
         let current_word = self.get_current_word();
-        match current_word.as_str() {
-            "return" => self.push_token(TokenKind::KEYWORD(KeywordKind::RETURN)),
-            _ => self.variable_declaration(current_word),
-        }
+        let kind = match current_word.as_str() {
+            "return" => TokenKind::KEYWORD(KeywordKind::RETURN),
+            "let" => TokenKind::KEYWORD(KeywordKind::LET),
+            "fn" => TokenKind::KEYWORD(KeywordKind::FN),
+            "if" => TokenKind::KEYWORD(KeywordKind::IF),
+            "else" => TokenKind::KEYWORD(KeywordKind::ELSE),
+            "while" => TokenKind::KEYWORD(KeywordKind::WHILE),
+            "for" => TokenKind::KEYWORD(KeywordKind::FOR),
+            "in" => TokenKind::KEYWORD(KeywordKind::IN),
+            "loop" => TokenKind::KEYWORD(KeywordKind::LOOP),
+            "break" => TokenKind::KEYWORD(KeywordKind::BREAK),
+            "continue" => TokenKind::KEYWORD(KeywordKind::CONTINUE),
+            "as" => TokenKind::KEYWORD(KeywordKind::AS),
+            "mut" => TokenKind::KEYWORD(KeywordKind::MUT),
+            "const" => TokenKind::KEYWORD(KeywordKind::CONST),
+            "static" => TokenKind::KEYWORD(KeywordKind::STATIC),
+            "struct" => TokenKind::KEYWORD(KeywordKind::STRUCT),
+            "enum" => TokenKind::KEYWORD(KeywordKind::ENUM),
+            "match" => TokenKind::KEYWORD(KeywordKind::MATCH),
+            "type" => TokenKind::KEYWORD(KeywordKind::TYPE),
+            "newtype" => TokenKind::KEYWORD(KeywordKind::NEWTYPE),
+            "null" => TokenKind::KEYWORD(KeywordKind::NULL),
+            "true" => TokenKind::KEYWORD(KeywordKind::TRUE),
+            "false" => TokenKind::KEYWORD(KeywordKind::FALSE),
+            _ => TokenKind::DECL_NAME(current_word),
+        };
+        self.push_token_at(kind, coord);
     }
 
     fn get_current_word(&mut self) -> String {
         let mut ret = String::new();
 
-        while !WHITESPACE.contains(&self.current_char) {
-            ret.push(self.current_char.clone());
+        while self.current_char.is_ascii_alphanumeric() || self.current_char == '_' {
+            ret.push(self.current_char);
             self.advance();
         }
         ret
     }
-
-    fn variable_declaration(&mut self, name: String) {
-        self.push_token(TokenKind::DECL_NAME(name));
-    }
 }
@@ -3,8 +3,16 @@
 #![allow(non_camel_case_types)]
 
 pub mod ast;
+pub mod builtins;
 pub mod compile;
+pub mod consteval;
 pub mod error;
+pub mod exhaustiveness;
 pub mod generator;
+pub mod layout;
 pub mod lexer;
 pub mod parser;
+pub mod semantic;
+pub mod typeck;
+pub mod types;
+pub mod typetable;
@@ -1,11 +1,19 @@
 use crate::ast::*;
 use crate::error::*;
 use crate::lexer::*;
+use crate::types::{Type, MAX_SIZE};
+use crate::typetable::TypeTable;
 
 pub struct Parser {
     tokens: Vec<Token>,
     tok_idx: usize,
     current_tok: Token,
+    // whether a name followed by `{` is a struct literal, which it can't be in the condition of
+    // an `if` or a loop, where the `{` starts the body instead
+    struct_literals: bool,
+    // every type declared in the program, so a name can be told apart as a struct, enum, alias
+    // or newtype before its declaration has been parsed
+    types: TypeTable,
 
     // DEBUG
     filepath: String,
@@ -13,16 +21,188 @@ pub struct Parser {
 
 impl Parser {
     pub fn new(tokens: Vec<Token>, filepath: String) -> Self {
+        let types = TypeTable::new(&tokens);
         Parser {
             tokens: tokens.clone(),
             tok_idx: 0,
             current_tok: tokens[0].clone(),
-            filepath: filepath,
+            struct_literals: true,
+            types,
+            filepath,
         }
     }
 
     pub fn parse(&mut self) -> Result<Node, Error> {
-        self.code_block()
+        self.program()
+    }
+
+    // the types declared in what has been parsed, for the semantic pass to resolve
+    pub fn into_types(self) -> TypeTable {
+        self.types
+    }
+
+    fn program(&mut self) -> Result<Node, Error> {
+        let mut nodes: Vec<Node> = Vec::new();
+        while self.current_tok.kind != TokenKind::EOF {
+            match self.current_tok.kind {
+                TokenKind::COMMENT(..) => self.advance(),
+                TokenKind::KEYWORD(KeywordKind::FN) => nodes.push(self.function()?),
+                TokenKind::KEYWORD(KeywordKind::STRUCT) => nodes.push(self.struct_decl()?),
+                TokenKind::KEYWORD(KeywordKind::ENUM) => nodes.push(self.enum_decl()?),
+                TokenKind::KEYWORD(KeywordKind::TYPE)
+                | TokenKind::KEYWORD(KeywordKind::NEWTYPE) => nodes.push(self.type_decl()?),
+                TokenKind::KEYWORD(KeywordKind::CONST)
+                | TokenKind::KEYWORD(KeywordKind::STATIC) => nodes.push(self.global_decl()?),
+                _ => {
+                    return Err(Error::FoundExpectedError {
+                        found: format!("{:?}", self.current_tok.kind),
+                        expected: "`fn`, `struct`, `enum`, `type`, `newtype`, `const` or `static`"
+                            .to_string(),
+                        coord: self.current_tok.coord,
+                        filepath: self.filepath.clone(),
+                    })
+                }
+            }
+        }
+        Ok(Node::Program { nodes })
+    }
+
+    fn function(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let name = self.expect_name()?;
+
+        self.expect(TokenKind::LPAREN)?;
+        let mut params: Vec<Node> = Vec::new();
+        while self.current_tok.kind != TokenKind::RPAREN {
+            if !params.is_empty() {
+                self.expect(TokenKind::COMMA)?;
+            }
+            let token = self.expect_name()?;
+            self.expect(TokenKind::COLON)?;
+            params.push(Node::Variable {
+                token,
+                offset: 0,
+                global: false,
+                ty: self.parse_type()?,
+            });
+        }
+        self.advance();
+
+        let ret_ty = if self.current_tok.kind == TokenKind::ARROW {
+            self.advance();
+            self.parse_type()?
+        } else {
+            Type::Unit
+        };
+
+        let body = self.code_block()?;
+        // step over the closing brace
+        self.advance();
+        Ok(Node::FnDecl {
+            token,
+            name,
+            params,
+            ret_ty,
+            body: Box::new(body),
+            frame_size: 0,
+        })
+    }
+
+    // the last field can have a comma after it
+    fn struct_decl(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let name = self.expect_name()?;
+        self.expect(TokenKind::CURLY_LPAREN)?;
+
+        let mut fields: Vec<(Token, Type)> = Vec::new();
+        while self.current_tok.kind != TokenKind::CURLY_RPAREN {
+            let field = self.expect_name()?;
+            self.expect(TokenKind::COLON)?;
+            fields.push((field, self.parse_type()?));
+            if self.current_tok.kind != TokenKind::CURLY_RPAREN {
+                self.expect(TokenKind::COMMA)?;
+            }
+        }
+        self.advance();
+        Ok(Node::StructDecl {
+            token,
+            name,
+            fields,
+        })
+    }
+
+    // `type Name = type;` or `newtype Name = type;`, where what it stands for is left for
+    // `TypeTable::resolve` to check
+    fn type_decl(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let name = self.expect_name()?;
+        self.expect(TokenKind::EQUALS)?;
+        let ty = self.parse_type()?;
+        self.expect(TokenKind::SEMICOLON)?;
+        self.types.define(name.clone(), ty.clone());
+        Ok(Node::TypeDecl { token, name, ty })
+    }
+
+    // like a struct, the last variant can have a comma after it, and so can the last field of
+    // a variant
+    fn enum_decl(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let name = self.expect_name()?;
+        self.expect(TokenKind::CURLY_LPAREN)?;
+
+        let mut variants: Vec<(Token, Vec<Type>)> = Vec::new();
+        while self.current_tok.kind != TokenKind::CURLY_RPAREN {
+            let variant = self.expect_name()?;
+            let mut fields = Vec::new();
+            if self.current_tok.kind == TokenKind::LPAREN {
+                self.advance();
+                while self.current_tok.kind != TokenKind::RPAREN {
+                    fields.push(self.parse_type()?);
+                    if self.current_tok.kind != TokenKind::RPAREN {
+                        self.expect(TokenKind::COMMA)?;
+                    }
+                }
+                self.advance();
+            }
+            variants.push((variant, fields));
+            if self.current_tok.kind != TokenKind::CURLY_RPAREN {
+                self.expect(TokenKind::COMMA)?;
+            }
+        }
+        self.advance();
+        Ok(Node::EnumDecl {
+            token,
+            name,
+            variants,
+        })
+    }
+
+    // unlike a local `let`, the type of a global always has to be written out
+    fn global_decl(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let mutable = token.kind == TokenKind::KEYWORD(KeywordKind::STATIC)
+            && self.current_tok.kind == TokenKind::KEYWORD(KeywordKind::MUT);
+        if mutable {
+            self.advance();
+        }
+        let name = self.expect_name()?;
+        self.expect(TokenKind::COLON)?;
+        let ty = self.parse_type()?;
+        self.expect(TokenKind::EQUALS)?;
+        let value = self.expr()?;
+        self.expect(TokenKind::SEMICOLON)?;
+        Ok(Node::GlobalDecl {
+            token,
+            name,
+            mutable,
+            ty,
+            value: Box::new(value),
+        })
     }
 
     fn code_block(&mut self) -> Result<Node, Error> {
@@ -35,66 +215,387 @@ impl Parser {
             self.advance();
             while self.current_tok.kind != TokenKind::CURLY_RPAREN {
                 nodes.push(match self.current_tok.kind {
-                    TokenKind::CURLY_LPAREN => self.scope()?,
-                    TokenKind::INT(..) => self.expr()?,
-                    TokenKind::COMMENT(..) => self.comment()?,
+                    TokenKind::CURLY_LPAREN => {
+                        let node = self.scope()?;
+                        self.advance();
+                        node
+                    }
+                    TokenKind::COMMENT(..) => {
+                        let node = self.comment()?;
+                        self.advance();
+                        node
+                    }
                     _ => self.stmt()?,
                 });
-                self.advance();
             }
-            Ok(Node::CompoundExpr { nodes: nodes })
+            Ok(Node::CompoundExpr { nodes })
         } else {
-            return Err(Error::FoundExpectedError {
+            Err(Error::FoundExpectedError {
                 found: format!("{:?}", self.current_tok.kind),
                 expected: format!("{:?}", TokenKind::CURLY_LPAREN),
                 coord: self.current_tok.coord,
                 filepath: self.filepath.clone(),
-            });
+            })
         }
     }
 
+    // A statement steps past what ends it, which is the `;` after most of them or the closing
+    // brace of the block an `if`, loop or `match` ends with.
     fn stmt(&mut self) -> Result<Node, Error> {
-        match self.current_tok.kind.clone() {
-            TokenKind::DECL_NAME(..) => return self.decl(),
-            TokenKind::KEYWORD(KeywordKind::RETURN) => return self.ret(),
-            _ => {
-                return Err(Error::IllegalSyntaxError {
-                    found: format!("{:?}", self.current_tok.kind),
-                    coord: self.current_tok.coord,
-                    filepath: self.filepath.clone(),
+        let node = match self.current_tok.kind.clone() {
+            TokenKind::KEYWORD(KeywordKind::RETURN) => self.ret()?,
+            TokenKind::KEYWORD(KeywordKind::LET) => self.let_decl()?,
+            TokenKind::KEYWORD(KeywordKind::CONST) => self.const_decl()?,
+            TokenKind::KEYWORD(KeywordKind::IF) => {
+                let node = self.if_stmt()?;
+                self.advance();
+                return Ok(node);
+            }
+            // a `match` on its own doesn't need a `;` after it, like an `if`
+            TokenKind::KEYWORD(KeywordKind::MATCH) => {
+                let node = self.match_expr()?;
+                self.advance();
+                if self.current_tok.kind == TokenKind::SEMICOLON {
+                    self.advance();
+                }
+                return Ok(node);
+            }
+            TokenKind::DECL_NAME(..) if self.peek().kind == TokenKind::COLON => {
+                let label = self.current_tok.clone();
+                self.advance();
+                self.advance();
+                let node = self.loop_stmt(Some(label))?;
+                self.advance();
+                return Ok(node);
+            }
+            TokenKind::KEYWORD(KeywordKind::WHILE)
+            | TokenKind::KEYWORD(KeywordKind::LOOP)
+            | TokenKind::KEYWORD(KeywordKind::FOR) => {
+                let node = self.loop_stmt(None)?;
+                self.advance();
+                return Ok(node);
+            }
+            TokenKind::KEYWORD(KeywordKind::BREAK) | TokenKind::KEYWORD(KeywordKind::CONTINUE) => {
+                self.loop_control()?
+            }
+            _ => self.assign()?,
+        };
+        self.expect(TokenKind::SEMICOLON)?;
+        Ok(node)
+    }
+
+    // an expression, which is assigned to if an `=` follows it. Whether it is something that
+    // can be assigned to is left to the semantic pass.
+    fn assign(&mut self) -> Result<Node, Error> {
+        let left = self.expr()?;
+        if self.current_tok.kind != TokenKind::EQUALS {
+            return Ok(left);
+        }
+        let token = self.current_tok.clone();
+        self.advance();
+        let right = self.expr()?;
+        Ok(Node::BinaryExpr {
+            token,
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+    }
+
+    fn let_decl(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let mutable = self.current_tok.kind == TokenKind::KEYWORD(KeywordKind::MUT);
+        if mutable {
+            self.advance();
+        }
+        let name = self.expect_name()?;
+        let ty = if self.current_tok.kind == TokenKind::COLON {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        self.expect(TokenKind::EQUALS)?;
+        let value = self.expr()?;
+        Ok(Node::LetDecl {
+            token,
+            name,
+            mutable,
+            ty,
+            value: Box::new(value),
+            offset: 0,
+        })
+    }
+
+    // a `const` has to have its type written out
+    fn const_decl(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let name = self.expect_name()?;
+        self.expect(TokenKind::COLON)?;
+        let ty = Some(self.parse_type()?);
+        self.expect(TokenKind::EQUALS)?;
+        let value = self.expr()?;
+        Ok(Node::LetDecl {
+            token,
+            name,
+            mutable: false,
+            ty,
+            value: Box::new(value),
+            offset: 0,
+        })
+    }
+
+    // like `scope`, this leaves the closing brace of the last block as the current token
+    fn if_stmt(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let condition = self.condition()?;
+        let then = self.scope()?;
+
+        let otherwise = if self.peek().kind == TokenKind::KEYWORD(KeywordKind::ELSE) {
+            self.advance();
+            self.advance();
+            match self.current_tok.kind {
+                TokenKind::KEYWORD(KeywordKind::IF) => Some(Box::new(self.if_stmt()?)),
+                _ => Some(Box::new(self.scope()?)),
+            }
+        } else {
+            None
+        };
+
+        Ok(Node::IfStmt {
+            token,
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise,
+        })
+    }
+
+    // like `scope`, this leaves the closing brace of the body as the current token
+    fn loop_stmt(&mut self, label: Option<Token>) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        match token.kind {
+            TokenKind::KEYWORD(KeywordKind::WHILE) => {
+                self.advance();
+                let condition = self.condition()?;
+                let body = self.scope()?;
+                Ok(Node::WhileStmt {
+                    token,
+                    label,
+                    condition: Some(Box::new(condition)),
+                    body: Box::new(body),
                 })
             }
+            TokenKind::KEYWORD(KeywordKind::LOOP) => {
+                self.advance();
+                let body = self.scope()?;
+                Ok(Node::WhileStmt {
+                    token,
+                    label,
+                    condition: None,
+                    body: Box::new(body),
+                })
+            }
+            TokenKind::KEYWORD(KeywordKind::FOR) => {
+                self.advance();
+                let variable = Node::Variable {
+                    token: self.expect_name()?,
+                    offset: 0,
+                    global: false,
+                    ty: Type::Unit,
+                };
+                self.expect(TokenKind::KEYWORD(KeywordKind::IN))?;
+                let start = self.condition()?;
+                // without a `..` it loops over the elements of an array or vec
+                if self.current_tok.kind != TokenKind::DOTDOT {
+                    let body = self.scope()?;
+                    return Ok(Node::ForEach {
+                        token,
+                        label,
+                        variable: Box::new(variable),
+                        iterable: Box::new(start),
+                        body: Box::new(body),
+                        offset: 0,
+                    });
+                }
+                self.advance();
+                let end = self.condition()?;
+                let body = self.scope()?;
+                Ok(Node::ForStmt {
+                    token,
+                    label,
+                    variable: Box::new(variable),
+                    start: Box::new(start),
+                    end: Box::new(end),
+                    body: Box::new(body),
+                    end_offset: 0,
+                })
+            }
+            _ => Err(Error::FoundExpectedError {
+                found: format!("{:?}", token.kind),
+                expected: "loop".to_string(),
+                coord: token.coord,
+                filepath: self.filepath.clone(),
+            }),
         }
     }
 
-    fn decl(&mut self) -> Result<Node, Error> {
-        let left = Node::Primary(self.current_tok.clone());
-        match self.current_tok.kind.clone() {
-            TokenKind::DECL_NAME(x) => {
-                println!("{}", x);
+    // An arm's value is either an expression followed by a comma, which the last arm can leave
+    // out, or a block, which doesn't need one. Like `scope`, this leaves the closing brace as
+    // the current token.
+    fn match_expr(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let node = self.condition()?;
+        self.expect(TokenKind::CURLY_LPAREN)?;
+
+        let mut arms: Vec<(Pattern, Node)> = Vec::new();
+        loop {
+            while let TokenKind::COMMENT(..) = self.current_tok.kind {
                 self.advance();
-                if self.current_tok.kind == TokenKind::EQUALS {
-                    let op_tok = self.current_tok.clone();
+            }
+            if self.current_tok.kind == TokenKind::CURLY_RPAREN {
+                break;
+            }
+            let pattern = self.pattern()?;
+            self.expect(TokenKind::FAT_ARROW)?;
+            let value = if self.current_tok.kind == TokenKind::CURLY_LPAREN {
+                let block = self.scope()?;
+                self.advance();
+                if self.current_tok.kind == TokenKind::COMMA {
+                    self.advance();
+                }
+                block
+            } else {
+                let value = self.bracketed_expr()?;
+                if self.current_tok.kind != TokenKind::CURLY_RPAREN {
+                    self.expect(TokenKind::COMMA)?;
+                }
+                value
+            };
+            arms.push((pattern, value));
+        }
+        Ok(Node::Match {
+            token,
+            node: Box::new(node),
+            arms,
+            ty: Type::Unit,
+            offset: 0,
+        })
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, Error> {
+        let token = self.current_tok.clone();
+        match &token.kind {
+            TokenKind::DECL_NAME(x) if x == "_" => {
+                self.advance();
+                Ok(Pattern::Wildcard(token))
+            }
+            TokenKind::DECL_NAME(..) if self.peek().kind == TokenKind::DOUBLE_COLON => {
+                self.advance();
+                self.advance();
+                let name = self.expect_name()?;
+                let mut fields = Vec::new();
+                if self.current_tok.kind == TokenKind::LPAREN {
+                    self.advance();
+                    while self.current_tok.kind != TokenKind::RPAREN {
+                        fields.push(self.pattern()?);
+                        if self.current_tok.kind != TokenKind::RPAREN {
+                            self.expect(TokenKind::COMMA)?;
+                        }
+                    }
                     self.advance();
-                    let right = self.expr()?;
-                    return Ok(Node::BinaryExpr {
-                        token: op_tok,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    });
-                } else {
-                    return Err(Error::FoundExpectedError {
-                        found: format!("{:?}", self.current_tok.kind.clone()),
-                        expected: format!("{:?}", TokenKind::EQUALS),
-                        coord: self.current_tok.coord,
-                        filepath: self.filepath.clone(),
-                    });
                 }
+                Ok(Pattern::Variant {
+                    token,
+                    name,
+                    fields,
+                })
+            }
+            TokenKind::DECL_NAME(..) => {
+                self.advance();
+                Ok(Pattern::Binding(Node::Variable {
+                    token,
+                    offset: 0,
+                    global: false,
+                    ty: Type::Unit,
+                }))
             }
-            _ => panic!("decl() called incorrectly"),
+            _ => {
+                let start = self.pattern_literal()?;
+                if self.current_tok.kind != TokenKind::DOTDOT {
+                    return Ok(Pattern::Literal(start));
+                }
+                let token = self.current_tok.clone();
+                self.advance();
+                let inclusive = self.current_tok.kind == TokenKind::EQUALS;
+                if inclusive {
+                    self.advance();
+                }
+                Ok(Pattern::Range {
+                    token,
+                    start: Box::new(start),
+                    end: Box::new(self.pattern_literal()?),
+                    inclusive,
+                })
+            }
+        }
+    }
+
+    fn pattern_literal(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        match token.kind {
+            TokenKind::INT(..)
+            | TokenKind::CHAR(..)
+            | TokenKind::KEYWORD(KeywordKind::TRUE | KeywordKind::FALSE) => {
+                self.advance();
+                Ok(Node::Primary(token))
+            }
+            TokenKind::MINUS if matches!(self.peek().kind, TokenKind::INT(..)) => {
+                self.advance();
+                let node = Node::Primary(self.current_tok.clone());
+                self.advance();
+                Ok(Node::UnaryExpr {
+                    token,
+                    node: Box::new(node),
+                })
+            }
+            kind => Err(Error::FoundExpectedError {
+                found: format!("{:?}", kind),
+                expected: "pattern".to_string(),
+                coord: token.coord,
+                filepath: self.filepath.clone(),
+            }),
         }
     }
 
+    // an expression followed by a block, so without struct literals outside of brackets
+    fn condition(&mut self) -> Result<Node, Error> {
+        let outer = std::mem::replace(&mut self.struct_literals, false);
+        let expr = self.expr();
+        self.struct_literals = outer;
+        expr
+    }
+
+    // an expression inside brackets of some kind, where struct literals are allowed again
+    fn bracketed_expr(&mut self) -> Result<Node, Error> {
+        let outer = std::mem::replace(&mut self.struct_literals, true);
+        let expr = self.expr();
+        self.struct_literals = outer;
+        expr
+    }
+
+    fn loop_control(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        let label = match self.current_tok.kind {
+            TokenKind::DECL_NAME(..) => Some(self.expect_name()?),
+            _ => None,
+        };
+        Ok(Node::LoopControl { token, label })
+    }
+
     fn comment(&mut self) -> Result<Node, Error> {
         let token = self.current_tok.clone();
         Ok(Node::Primary(token))
@@ -103,37 +604,140 @@ impl Parser {
     fn ret(&mut self) -> Result<Node, Error> {
         let ret = self.current_tok.clone();
         self.advance();
-        let expr = self.expr()?;
+        // a bare `return;` returns nothing, which is an empty block
+        let expr = if self.current_tok.kind == TokenKind::SEMICOLON {
+            Node::CompoundExpr { nodes: Vec::new() }
+        } else {
+            self.expr()?
+        };
         Ok(Node::UnaryExpr {
             token: ret,
             node: Box::new(expr),
         })
     }
 
+    // Each precedence level is a chain of binary operators over the level below it, from
+    // loosest to tightest: || && | ^ & (== !=) (< <= > >=) (<< >> >>>) (+ -) (* / %)
     fn expr(&mut self) -> Result<Node, Error> {
-        let mut left = self.term()?;
+        self.binary_level(&[TokenKind::OR], Parser::logical_and)
+    }
 
-        while vec![TokenKind::PLUS, TokenKind::MINUS].contains(&self.current_tok.kind) {
-            let op_tok = self.current_tok.clone();
+    fn logical_and(&mut self) -> Result<Node, Error> {
+        self.binary_level(&[TokenKind::AND], Parser::bit_or)
+    }
+
+    fn bit_or(&mut self) -> Result<Node, Error> {
+        self.binary_level(&[TokenKind::BIT_OR], Parser::bit_xor)
+    }
+
+    fn bit_xor(&mut self) -> Result<Node, Error> {
+        self.binary_level(&[TokenKind::BIT_XOR], Parser::bit_and)
+    }
+
+    fn bit_and(&mut self) -> Result<Node, Error> {
+        self.binary_level(&[TokenKind::BIT_AND], Parser::equality)
+    }
+
+    fn equality(&mut self) -> Result<Node, Error> {
+        self.binary_level(
+            &[TokenKind::DOUBLE_EQUALS, TokenKind::NOT_EQUALS],
+            Parser::comparison,
+        )
+    }
+
+    fn comparison(&mut self) -> Result<Node, Error> {
+        self.binary_level(
+            &[
+                TokenKind::LESS_THAN,
+                TokenKind::LESS_EQUALS,
+                TokenKind::GREATER_THAN,
+                TokenKind::GREATER_EQUALS,
+            ],
+            Parser::shift,
+        )
+    }
+
+    fn shift(&mut self) -> Result<Node, Error> {
+        self.binary_level(
+            &[
+                TokenKind::SHIFT_LEFT,
+                TokenKind::SHIFT_RIGHT,
+                TokenKind::UNSIGNED_SHIFT_RIGHT,
+            ],
+            Parser::arith_expr,
+        )
+    }
+
+    fn arith_expr(&mut self) -> Result<Node, Error> {
+        self.binary_level(&[TokenKind::PLUS, TokenKind::MINUS], Parser::term)
+    }
+
+    fn term(&mut self) -> Result<Node, Error> {
+        self.binary_level(
+            &[TokenKind::MUL, TokenKind::DIV, TokenKind::MOD],
+            Parser::cast,
+        )
+    }
+
+    fn cast(&mut self) -> Result<Node, Error> {
+        let mut node = self.postfix()?;
+
+        while self.current_tok.kind == TokenKind::KEYWORD(KeywordKind::AS) {
+            let token = self.current_tok.clone();
             self.advance();
-            let right = self.term()?;
-            left = Node::BinaryExpr {
-                token: op_tok,
-                left: Box::new(left),
-                right: Box::new(right),
+            node = Node::Cast {
+                token,
+                node: Box::new(node),
+                ty: self.parse_type()?,
             };
         }
 
-        Ok(left)
+        Ok(node)
     }
 
-    fn term(&mut self) -> Result<Node, Error> {
-        let mut left = self.factor()?;
+    // indexing binds tighter than any other operator, so `-a[0]` is `-(a[0])`
+    fn postfix(&mut self) -> Result<Node, Error> {
+        let mut node = self.factor()?;
+
+        loop {
+            let token = self.current_tok.clone();
+            node = match token.kind {
+                TokenKind::SQUARE_LPAREN => {
+                    self.advance();
+                    let index = self.bracketed_expr()?;
+                    self.expect(TokenKind::SQUARE_RPAREN)?;
+                    Node::Index {
+                        token,
+                        array: Box::new(node),
+                        index: Box::new(index),
+                        ty: Type::Unit,
+                    }
+                }
+                TokenKind::DOT => {
+                    self.advance();
+                    Node::Field {
+                        token,
+                        node: Box::new(node),
+                        name: self.expect_name()?,
+                        ty: Type::Unit,
+                    }
+                }
+                _ => return Ok(node),
+            };
+        }
+    }
+
+    fn binary_level(
+        &mut self,
+        ops: &[TokenKind],
+        next: fn(&mut Parser) -> Result<Node, Error>,
+    ) -> Result<Node, Error> {
+        let mut left = next(self)?;
 
-        while vec![TokenKind::MUL, TokenKind::DIV].contains(&self.current_tok.kind) {
+        while ops.contains(&self.current_tok.kind) {
             let op_tok = self.current_tok.clone();
             self.advance();
-            let right = self.factor()?;
+            let right = next(self)?;
             left = Node::BinaryExpr {
                 token: op_tok,
                 left: Box::new(left),
@@ -148,47 +752,261 @@ impl Parser {
     fn factor(&mut self) -> Result<Node, Error> {
         let tok = self.current_tok.clone();
         match self.current_tok.kind {
-            TokenKind::INT(..) => {
+            TokenKind::INT(..)
+            | TokenKind::FLOAT(..)
+            | TokenKind::STR(..)
+            | TokenKind::CHAR(..)
+            | TokenKind::KEYWORD(KeywordKind::TRUE | KeywordKind::FALSE | KeywordKind::NULL) => {
+                self.advance();
+                Ok(Node::Primary(tok))
+            }
+            TokenKind::DECL_NAME(..) if self.peek().kind == TokenKind::LPAREN => self.call(),
+            TokenKind::DECL_NAME(..) if self.peek().kind == TokenKind::DOUBLE_COLON => {
+                self.variant()
+            }
+            TokenKind::DECL_NAME(..)
+                if self.struct_literals && self.peek().kind == TokenKind::CURLY_LPAREN =>
+            {
+                self.struct_literal()
+            }
+            TokenKind::DECL_NAME(..) => {
                 self.advance();
-                return Ok(Node::Primary(tok));
+                Ok(Node::Variable {
+                    token: tok,
+                    offset: 0,
+                    global: false,
+                    ty: Type::Unit,
+                })
             }
-            TokenKind::PLUS | TokenKind::MINUS => {
+            // in front of something, `*` dereferences it and `&` takes its address rather than
+            // multiplying or and-ing
+            TokenKind::PLUS
+            | TokenKind::MINUS
+            | TokenKind::NOT
+            | TokenKind::BIT_NOT
+            | TokenKind::MUL
+            | TokenKind::BIT_AND => {
                 self.advance();
-                let factor = self.factor();
-                return Ok(Node::UnaryExpr {
+                let factor = self.postfix();
+                Ok(Node::UnaryExpr {
                     token: tok,
                     node: Box::new(factor?),
-                });
+                })
+            }
+            TokenKind::SQUARE_LPAREN => self.array_literal(),
+            TokenKind::KEYWORD(KeywordKind::MATCH) => {
+                let node = self.match_expr()?;
+                self.advance();
+                Ok(node)
             }
             TokenKind::LPAREN => {
                 self.advance();
-                let expr = self.expr();
+                let expr = self.bracketed_expr();
                 if self.current_tok.kind == TokenKind::RPAREN {
                     self.advance();
-                    return expr;
+                    expr
                 } else {
-                    return Err(Error::FoundExpectedError {
+                    Err(Error::FoundExpectedError {
                         found: format!("{:?}", self.current_tok.kind),
                         expected: ")".to_string(),
                         filepath: self.current_tok.filepath.clone(),
                         coord: self.current_tok.coord,
-                    });
+                    })
                 }
             }
-            _ => {
-                return Err(Error::IllegalSyntaxError {
-                    found: format!("{:?}", self.current_tok.kind),
-                    filepath: self.current_tok.filepath.clone(),
-                    coord: self.current_tok.coord,
-                })
+            _ => Err(Error::IllegalSyntaxError {
+                found: format!("{:?}", self.current_tok.kind),
+                filepath: self.current_tok.filepath.clone(),
+                coord: self.current_tok.coord,
+            }),
+        }
+    }
+
+    fn array_literal(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+
+        let mut elements: Vec<Node> = Vec::new();
+        while self.current_tok.kind != TokenKind::SQUARE_RPAREN {
+            if !elements.is_empty() {
+                self.expect(TokenKind::COMMA)?;
+            }
+            elements.push(self.bracketed_expr()?);
+        }
+        self.advance();
+        Ok(Node::ArrayLiteral {
+            token,
+            elements,
+            ty: Type::Unit,
+            offset: 0,
+        })
+    }
+
+    fn call(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        self.advance();
+
+        let mut args: Vec<Node> = Vec::new();
+        while self.current_tok.kind != TokenKind::RPAREN {
+            if !args.is_empty() {
+                self.expect(TokenKind::COMMA)?;
+            }
+            args.push(self.bracketed_expr()?);
+        }
+        self.advance();
+        Ok(Node::Call {
+            token,
+            args,
+            ty: Type::Unit,
+            offset: 0,
+        })
+    }
+
+    // `Name::Variant`, with its fields in brackets after it if it has any
+    fn variant(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        self.advance();
+        let name = self.expect_name()?;
+
+        let mut args: Vec<Node> = Vec::new();
+        if self.current_tok.kind == TokenKind::LPAREN {
+            self.advance();
+            while self.current_tok.kind != TokenKind::RPAREN {
+                if !args.is_empty() {
+                    self.expect(TokenKind::COMMA)?;
+                }
+                args.push(self.bracketed_expr()?);
+            }
+            self.advance();
+        }
+        Ok(Node::Variant {
+            token,
+            name,
+            args,
+            ty: Type::Unit,
+            offset: 0,
+        })
+    }
+
+    // like a struct declaration, the last field can have a comma after it
+    fn struct_literal(&mut self) -> Result<Node, Error> {
+        let token = self.current_tok.clone();
+        self.advance();
+        self.advance();
+
+        let mut fields: Vec<(Token, Node)> = Vec::new();
+        while self.current_tok.kind != TokenKind::CURLY_RPAREN {
+            let field = self.expect_name()?;
+            self.expect(TokenKind::COLON)?;
+            fields.push((field, self.bracketed_expr()?));
+            if self.current_tok.kind != TokenKind::CURLY_RPAREN {
+                self.expect(TokenKind::COMMA)?;
+            }
+        }
+        self.advance();
+        Ok(Node::StructLiteral {
+            token,
+            fields,
+            ty: Type::Unit,
+            offset: 0,
+        })
+    }
+
+    fn parse_type(&mut self) -> Result<Type, Error> {
+        let tok = self.current_tok.clone();
+        // `[T; N]`, where `N` has to be written out as a number, or `[T]` for a vec
+        if tok.kind == TokenKind::SQUARE_LPAREN {
+            self.advance();
+            let elem = self.parse_type()?;
+            if self.current_tok.kind == TokenKind::SQUARE_RPAREN {
+                self.advance();
+                return Ok(Type::Vec(Box::new(elem)));
+            }
+            self.expect(TokenKind::SEMICOLON)?;
+            let len = match self.current_tok.kind.clone() {
+                // too long to fit in `MAX_SIZE` bytes, unless its elements take up none at all
+                TokenKind::INT(IntLiteral { value, ty: None }) if value > MAX_SIZE as u64 => {
+                    return Err(Error::TypeError {
+                        found: format!("`[{}; {}]`", elem, value),
+                        expected: format!("type of at most {} bytes", MAX_SIZE),
+                        coord: self.current_tok.coord,
+                        filepath: self.filepath.clone(),
+                    })
+                }
+                TokenKind::INT(IntLiteral { value, ty: None }) => value as usize,
+                kind => {
+                    return Err(Error::FoundExpectedError {
+                        found: format!("{:?}", kind),
+                        expected: "array length".to_string(),
+                        coord: self.current_tok.coord,
+                        filepath: self.filepath.clone(),
+                    })
+                }
+            };
+            self.advance();
+            self.expect(TokenKind::SQUARE_RPAREN)?;
+            return Ok(Type::Array(Box::new(elem), len));
+        }
+        // `*T`
+        if tok.kind == TokenKind::MUL {
+            self.advance();
+            return Ok(Type::Pointer(Box::new(self.parse_type()?)));
+        }
+        if let TokenKind::DECL_NAME(x) = &tok.kind {
+            self.advance();
+            return Ok(self.types.type_of(x));
+        }
+        Err(Error::FoundExpectedError {
+            found: format!("{:?}", tok.kind),
+            expected: "type".to_string(),
+            coord: tok.coord,
+            filepath: self.filepath.clone(),
+        })
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, Error> {
+        let tok = self.current_tok.clone();
+        if tok.kind == kind {
+            self.advance();
+            Ok(tok)
+        } else {
+            Err(Error::FoundExpectedError {
+                found: format!("{:?}", tok.kind),
+                expected: format!("{:?}", kind),
+                coord: tok.coord,
+                filepath: self.filepath.clone(),
+            })
+        }
+    }
+
+    fn expect_name(&mut self) -> Result<Token, Error> {
+        let tok = self.current_tok.clone();
+        match tok.kind {
+            TokenKind::DECL_NAME(..) => {
+                self.advance();
+                Ok(tok)
             }
+            _ => Err(Error::FoundExpectedError {
+                found: format!("{:?}", tok.kind),
+                expected: "name".to_string(),
+                coord: tok.coord,
+                filepath: self.filepath.clone(),
+            }),
         }
     }
 
     fn advance(&mut self) {
         self.tok_idx += 1;
         if self.tok_idx < self.tokens.len() {
-            self.current_tok = self.tokens[self.tok_idx as usize].clone();
+            self.current_tok = self.tokens[self.tok_idx].clone();
         }
     }
+
+    fn peek(&self) -> &Token {
+        self.tokens
+            .get(self.tok_idx + 1)
+            .unwrap_or_else(|| self.tokens.last().unwrap())
+    }
 }
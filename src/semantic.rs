@@ -0,0 +1,1027 @@
+use crate::ast::*;
+use crate::builtins::Builtin;
+use crate::error::*;
+use crate::layout::Layouts;
+use crate::lexer::*;
+use crate::types::{Type, MAX_SIZE};
+use crate::typetable::TypeTable;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, PartialEq)]
+enum Binding {
+    Immutable,
+    Mutable,
+    Constant,
+}
+
+// `offset` means nothing for a `global`, which is found by its name instead
+#[derive(Clone)]
+struct Local {
+    offset: i64,
+    global: bool,
+    ty: Type,
+    binding: Binding,
+    // the name where it was declared, for pointing errors at
+    name: Token,
+}
+
+struct Signature {
+    params: Vec<Type>,
+    ret_ty: Type,
+}
+
+// Resolves every variable to a stack slot and gives it its type. Each `{ }` block gets its
+// own scope, so names declared inside it shadow outer ones and stop existing once the block
+// ends, at which point its slots are handed back to be reused by whatever comes next. It
+// also makes sure only `let mut` variables are assigned to.
+pub struct Analyser {
+    scopes: Vec<HashMap<String, Local>>,
+    stack_size: i64,
+    max_stack_size: i64,
+
+    functions: HashMap<String, Signature>,
+    // the fields of every struct and the variants of every enum as they were declared, and
+    // which of them have been laid out
+    structs: HashMap<String, Vec<(Token, Type)>>,
+    enums: HashMap<String, Vec<(Token, Vec<Type>)>>,
+    laid_out: HashSet<String>,
+    layouts: Layouts,
+    // what every alias and newtype stands for, which `resolve` has checked
+    types: TypeTable,
+    // labels of the loops we are currently inside, innermost last
+    loops: Vec<Option<String>>,
+    // how many `Type::Infer`s have been handed out
+    type_vars: usize,
+}
+
+impl Analyser {
+    pub fn new(types: TypeTable) -> Self {
+        Self {
+            scopes: Vec::new(),
+            stack_size: 0,
+            max_stack_size: 0,
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            laid_out: HashSet::new(),
+            layouts: Layouts::new(),
+            types,
+            loops: Vec::new(),
+            type_vars: 0,
+        }
+    }
+
+    pub fn analyse(&mut self, ast: &mut Node) -> Result<(), Error> {
+        self.analyse_node(ast)
+    }
+
+    // the layouts of every struct and enum, for the passes after this one
+    pub fn layouts(&self) -> &Layouts {
+        &self.layouts
+    }
+
+    fn analyse_node(&mut self, node: &mut Node) -> Result<(), Error> {
+        match node {
+            Node::Primary(..) => Ok(()),
+            Node::Variable {
+                token,
+                offset,
+                global,
+                ty,
+            } => {
+                let local = self.lookup(token)?;
+                *offset = local.offset;
+                *global = local.global;
+                *ty = local.ty;
+                Ok(())
+            }
+            Node::UnaryExpr { token, node } => {
+                self.analyse_node(node)?;
+                if token.kind == TokenKind::BIT_AND && place(node).is_none() {
+                    let token = node.token().unwrap_or(token);
+                    return Err(Error::TypeError {
+                        found: "expression".to_string(),
+                        expected: "variable, index, field or dereference to take the address of"
+                            .to_string(),
+                        filepath: token.filepath.clone(),
+                        coord: token.coord,
+                    });
+                }
+                Ok(())
+            }
+            Node::Cast { token, node, ty } => {
+                self.resolve_type(ty, token)?;
+                self.analyse_node(node)
+            }
+            Node::BinaryExpr { token, left, right } => {
+                self.analyse_node(right)?;
+                self.analyse_node(left)?;
+                if token.kind != TokenKind::EQUALS {
+                    return Ok(());
+                }
+                // only a variable, or part of one, can be assigned to, and only if it is `mut`.
+                // What a pointer points at, or an element of a vec, can always be.
+                let token = match place(left) {
+                    Some(Place::Variable(token)) => token,
+                    Some(Place::Pointer) => return Ok(()),
+                    None => {
+                        let token = left.token().unwrap_or(token);
+                        return Err(Error::TypeError {
+                            found: "expression".to_string(),
+                            expected: "variable, index or field to assign to".to_string(),
+                            filepath: token.filepath.clone(),
+                            coord: token.coord,
+                        });
+                    }
+                };
+                let local = self.lookup(token)?;
+                if local.binding != Binding::Mutable {
+                    return Err(Error::MutabilityError {
+                        name: name_of(token),
+                        filepath: token.filepath.clone(),
+                        coord: token.coord,
+                        declared: local.name.coord,
+                    });
+                }
+                Ok(())
+            }
+            Node::ArrayLiteral {
+                elements,
+                ty,
+                offset,
+                ..
+            } => {
+                for element in elements.iter_mut() {
+                    self.analyse_node(element)?;
+                }
+                let elem = match elements.first() {
+                    Some(first) => self.infer_type(first),
+                    None => {
+                        self.type_vars += 1;
+                        Type::Infer(self.type_vars)
+                    }
+                };
+                *ty = Type::Array(Box::new(elem), elements.len());
+                *offset = self.allocate(ty);
+                Ok(())
+            }
+            // what is being indexed has to be an array, which the type checker makes sure of
+            Node::Index {
+                array, index, ty, ..
+            } => {
+                self.analyse_node(array)?;
+                self.analyse_node(index)?;
+                if let Type::Array(elem, ..) | Type::Vec(elem) = array.ty().repr() {
+                    *ty = *elem;
+                }
+                Ok(())
+            }
+            Node::StructLiteral {
+                token,
+                fields,
+                ty,
+                offset,
+            } => {
+                let name = name_of(token);
+                let declared = match self.structs.get(&name) {
+                    Some(x) => x.clone(),
+                    None => {
+                        return Err(Error::UndeclaredNameError {
+                            name,
+                            filepath: token.filepath.clone(),
+                            coord: token.coord,
+                        })
+                    }
+                };
+                // every field has to be given exactly once
+                for (i, (field, _)) in fields.iter().enumerate() {
+                    let x = name_of(field);
+                    if !declared.iter().any(|(y, _)| name_of(y) == x) {
+                        return Err(no_field_error(field, &name));
+                    }
+                    if fields[..i].iter().any(|(y, _)| name_of(y) == x) {
+                        return Err(Error::RedefinitionError {
+                            name: x,
+                            filepath: field.filepath.clone(),
+                            coord: field.coord,
+                        });
+                    }
+                }
+                for (field, _) in declared.iter() {
+                    let x = name_of(field);
+                    if !fields.iter().any(|(y, _)| name_of(y) == x) {
+                        return Err(Error::TypeError {
+                            found: format!("`{}` without `{}`", name, x),
+                            expected: "a value for every field".to_string(),
+                            filepath: token.filepath.clone(),
+                            coord: token.coord,
+                        });
+                    }
+                }
+
+                for (_, value) in fields.iter_mut() {
+                    self.analyse_node(value)?;
+                }
+                *ty = Type::Struct(name);
+                *offset = self.allocate(ty);
+                Ok(())
+            }
+            // what has its field taken has to be a struct, which the type checker makes sure of
+            Node::Field { node, name, ty, .. } => {
+                self.analyse_node(node)?;
+                if let Type::Struct(x) = node.ty().repr() {
+                    match self.layouts.of(&x).field(&name_of(name)) {
+                        Some(field) => *ty = field.ty.clone(),
+                        None => return Err(no_field_error(name, &x)),
+                    }
+                }
+                Ok(())
+            }
+            Node::Variant {
+                token,
+                name,
+                args,
+                ty,
+                offset,
+            } => {
+                let fields = self.variant_fields(token, name)?;
+                if fields.len() != args.len() {
+                    return Err(Error::TypeError {
+                        found: format!("{} fields", args.len()),
+                        expected: format!("{} fields", fields.len()),
+                        filepath: name.filepath.clone(),
+                        coord: name.coord,
+                    });
+                }
+                for arg in args.iter_mut() {
+                    self.analyse_node(arg)?;
+                }
+                *ty = Type::Enum(name_of(token));
+                *offset = self.allocate(ty);
+                Ok(())
+            }
+            // Each arm is a scope of its own holding the names its pattern binds. Its slots
+            // aren't handed back afterwards, as the value of the arm could be in one of them.
+            Node::Match {
+                node,
+                arms,
+                ty,
+                offset,
+                ..
+            } => {
+                self.analyse_node(node)?;
+                let matched = self.infer_type(node);
+                *offset = self.allocate(&Type::U64);
+                for (pattern, value) in arms.iter_mut() {
+                    self.scopes.push(HashMap::new());
+                    self.analyse_pattern(pattern, &matched)?;
+                    self.analyse_node(value)?;
+                    self.scopes.pop();
+                }
+                // a block has no value, so it is left to the type checker to make sure that
+                // the others agree with it
+                *ty = match arms
+                    .iter()
+                    .find(|(_, x)| !matches!(x, Node::CompoundExpr { .. }))
+                {
+                    Some((_, value)) => self.infer_type(value),
+                    None => Type::Unit,
+                };
+                Ok(())
+            }
+            Node::StructDecl { .. } | Node::EnumDecl { .. } | Node::TypeDecl { .. } => Ok(()),
+            Node::LetDecl {
+                token,
+                name,
+                mutable,
+                ty,
+                value,
+                offset,
+            } => {
+                if let Some(ty) = ty {
+                    self.resolve_type(ty, name)?;
+                }
+                // the initialiser is resolved first, so `let x = x + 1;` refers to the outer `x`
+                self.analyse_node(value)?;
+                let binding = if token.kind == TokenKind::KEYWORD(KeywordKind::CONST) {
+                    self.expect_constant(value)?;
+                    Binding::Constant
+                } else if *mutable {
+                    Binding::Mutable
+                } else {
+                    Binding::Immutable
+                };
+                let ty = ty.get_or_insert_with(|| self.infer_type(value)).clone();
+                *offset = self.declare(name, ty, binding).offset;
+                Ok(())
+            }
+            Node::Program { nodes } => {
+                // structs and enums can be used before they are declared too, so they are all
+                // collected and laid out before anything else
+                for node in nodes.iter_mut() {
+                    match node {
+                        Node::StructDecl { name, fields, .. } => {
+                            for (_, ty) in fields.iter_mut() {
+                                *ty = self.types.fill_in(ty);
+                            }
+                            let names: Vec<&Token> = fields.iter().map(|(x, _)| x).collect();
+                            self.expect_new_type(name, &names)?;
+                            self.structs.insert(name_of(name), fields.clone());
+                        }
+                        Node::EnumDecl { name, variants, .. } => {
+                            for ty in variants.iter_mut().flat_map(|(_, x)| x.iter_mut()) {
+                                *ty = self.types.fill_in(ty);
+                            }
+                            let names: Vec<&Token> = variants.iter().map(|(x, _)| x).collect();
+                            self.expect_new_type(name, &names)?;
+                            self.enums.insert(name_of(name), variants.clone());
+                        }
+                        _ => (),
+                    }
+                }
+                for node in nodes.iter() {
+                    if let Node::StructDecl { name, .. } | Node::EnumDecl { name, .. } = node {
+                        self.lay_out(&name_of(name), &mut Vec::new())?;
+                    }
+                }
+                // what a pointer in one points at can be any of them, so is only checked once
+                // they all have a size
+                let fields = self.structs.values().flatten().cloned().chain(
+                    self.enums.values().flatten().flat_map(|(variant, fields)| {
+                        fields.iter().map(move |ty| (variant.clone(), ty.clone()))
+                    }),
+                );
+                for (field, ty) in fields {
+                    self.expect_sized(&ty, &field)?;
+                }
+
+                // functions can be called before they are defined, so collect them all first
+                for node in nodes.iter_mut() {
+                    if let Node::FnDecl {
+                        name,
+                        params,
+                        ret_ty,
+                        ..
+                    } = node
+                    {
+                        for param in params.iter_mut() {
+                            if let Node::Variable { token, ty, .. } = param {
+                                self.resolve_type(ty, token)?;
+                            }
+                        }
+                        self.resolve_type(ret_ty, name)?;
+                        let signature = Signature {
+                            params: params.iter().map(|x| x.ty()).collect(),
+                            ret_ty: ret_ty.clone(),
+                        };
+                        self.expect_undefined(name)?;
+                        self.functions.insert(name_of(name), signature);
+                    }
+                }
+                // globals go in the outermost scope, and are done first so every function can
+                // see them. Their initialisers can only use the ones declared above them.
+                self.scopes.push(HashMap::new());
+                let (globals, functions): (Vec<_>, Vec<_>) = nodes
+                    .iter_mut()
+                    .partition(|x| matches!(x, Node::GlobalDecl { .. }));
+                for node in globals.into_iter().chain(functions) {
+                    self.analyse_node(node)?;
+                }
+                self.scopes.pop();
+                Ok(())
+            }
+            Node::GlobalDecl {
+                token,
+                name,
+                mutable,
+                ty,
+                value,
+            } => {
+                self.expect_undefined(name)?;
+                self.resolve_type(ty, name)?;
+                self.analyse_node(value)?;
+                self.expect_constant(value)?;
+                let binding = if token.kind == TokenKind::KEYWORD(KeywordKind::CONST) {
+                    Binding::Constant
+                } else if *mutable {
+                    Binding::Mutable
+                } else {
+                    Binding::Immutable
+                };
+                let local = Local {
+                    offset: 0,
+                    global: true,
+                    ty: ty.clone(),
+                    binding,
+                    name: name.clone(),
+                };
+                self.scopes
+                    .last_mut()
+                    .expect("global outside of the program")
+                    .insert(name_of(name), local);
+                Ok(())
+            }
+            Node::FnDecl {
+                params,
+                body,
+                frame_size,
+                ..
+            } => {
+                self.stack_size = 0;
+                self.max_stack_size = 0;
+                self.scopes.push(HashMap::new());
+                for param in params {
+                    if let Node::Variable {
+                        token, offset, ty, ..
+                    } = param
+                    {
+                        *offset = self.declare(token, ty.clone(), Binding::Immutable).offset;
+                    }
+                }
+                self.analyse_node(body)?;
+                self.scopes.pop();
+                *frame_size = (self.max_stack_size + 15) / 16 * 16;
+                Ok(())
+            }
+            Node::Call {
+                token,
+                args,
+                ty,
+                offset,
+            } => {
+                let name = name_of(token);
+                match self.functions.get(&name) {
+                    None if Builtin::from_name(&name).is_some() => {
+                        let (min, max) = Builtin::from_name(&name).unwrap().arity();
+                        if args.len() < min || args.len() > max {
+                            let expected = if min == max {
+                                format!("{} arguments", min)
+                            } else {
+                                format!("{} to {} arguments", min, max)
+                            };
+                            return Err(Error::TypeError {
+                                found: format!("{} arguments", args.len()),
+                                expected,
+                                filepath: token.filepath.clone(),
+                                coord: token.coord,
+                            });
+                        }
+                        *ty = Builtin::from_name(&name).unwrap().ty();
+                    }
+                    None => {
+                        return Err(Error::UndeclaredNameError {
+                            name,
+                            filepath: token.filepath.clone(),
+                            coord: token.coord,
+                        })
+                    }
+                    Some(signature) if signature.params.len() != args.len() => {
+                        return Err(Error::TypeError {
+                            found: format!("{} arguments", args.len()),
+                            expected: format!("{} arguments", signature.params.len()),
+                            filepath: token.filepath.clone(),
+                            coord: token.coord,
+                        })
+                    }
+                    Some(signature) => *ty = signature.ret_ty.clone(),
+                }
+                for arg in args.iter_mut() {
+                    self.analyse_node(arg)?;
+                }
+                // `pop` returns whatever its vec holds, which is only known once the vec is
+                if Builtin::from_name(&name) == Some(Builtin::Pop) {
+                    if let Type::Vec(elem) = args[0].ty().repr() {
+                        *ty = *elem;
+                    }
+                }
+                if ty.is_aggregate() {
+                    *offset = self.allocate(ty);
+                }
+                Ok(())
+            }
+            Node::IfStmt {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.analyse_node(condition)?;
+                self.analyse_node(then)?;
+                if let Some(otherwise) = otherwise {
+                    self.analyse_node(otherwise)?;
+                }
+                Ok(())
+            }
+            Node::WhileStmt {
+                label,
+                condition,
+                body,
+                ..
+            } => {
+                if let Some(condition) = condition {
+                    self.analyse_node(condition)?;
+                }
+                self.analyse_loop_body(label, body)
+            }
+            Node::ForStmt {
+                label,
+                variable,
+                start,
+                end,
+                body,
+                end_offset,
+                ..
+            } => {
+                self.analyse_node(start)?;
+                self.analyse_node(end)?;
+
+                // the loop variable only exists inside the loop, and the upper bound is kept
+                // in a slot without a name next to it
+                self.scopes.push(HashMap::new());
+                let stack_size = self.stack_size;
+                let loop_ty = if start.is_untyped_literal() {
+                    self.infer_type(end)
+                } else {
+                    Node::operand_type(start, end)
+                };
+                if let Node::Variable {
+                    token, offset, ty, ..
+                } = variable.as_mut()
+                {
+                    *offset = self
+                        .declare(token, loop_ty.clone(), Binding::Immutable)
+                        .offset;
+                    *ty = loop_ty.clone();
+                }
+                *end_offset = self.allocate(&loop_ty);
+
+                self.analyse_loop_body(label, body)?;
+                self.stack_size = stack_size;
+                self.scopes.pop();
+                Ok(())
+            }
+            // what is looped over has to be an array or vec, which the type checker makes sure of
+            Node::ForEach {
+                label,
+                variable,
+                iterable,
+                body,
+                offset,
+                ..
+            } => {
+                self.analyse_node(iterable)?;
+
+                self.scopes.push(HashMap::new());
+                let stack_size = self.stack_size;
+                let elem = match iterable.ty().repr() {
+                    Type::Array(elem, ..) | Type::Vec(elem) => *elem,
+                    _ => Type::Unit,
+                };
+                if let Node::Variable {
+                    token, offset, ty, ..
+                } = variable.as_mut()
+                {
+                    *offset = self.declare(token, elem.clone(), Binding::Immutable).offset;
+                    *ty = elem;
+                }
+                *offset = self.allocate(&Type::I64);
+                self.allocate(&Type::I64);
+
+                self.analyse_loop_body(label, body)?;
+                self.stack_size = stack_size;
+                self.scopes.pop();
+                Ok(())
+            }
+            Node::LoopControl { token, label } => {
+                let keyword = match token.kind {
+                    TokenKind::KEYWORD(KeywordKind::BREAK) => "break",
+                    _ => "continue",
+                };
+                if self.loops.is_empty() {
+                    return Err(Error::LoopControlError {
+                        keyword: keyword.to_string(),
+                        filepath: token.filepath.clone(),
+                        coord: token.coord,
+                    });
+                }
+                if let Some(label) = label {
+                    let name = name_of(label);
+                    if !self.loops.contains(&Some(name.clone())) {
+                        return Err(Error::UndeclaredNameError {
+                            name,
+                            filepath: label.filepath.clone(),
+                            coord: label.coord,
+                        });
+                    }
+                }
+                Ok(())
+            }
+            Node::CompoundExpr { nodes } => {
+                self.scopes.push(HashMap::new());
+                let stack_size = self.stack_size;
+                for node in nodes {
+                    self.analyse_node(node)?;
+                }
+                self.stack_size = stack_size;
+                self.scopes.pop();
+                Ok(())
+            }
+        }
+    }
+
+    fn analyse_loop_body(&mut self, label: &Option<Token>, body: &mut Node) -> Result<(), Error> {
+        self.loops.push(label.as_ref().map(name_of));
+        let result = self.analyse_node(body);
+        self.loops.pop();
+        result
+    }
+
+    // the type of a variable declared without an annotation. A bare literal could be any
+    // integer type, so that is left to the type checker to work out from the rest of the function
+    fn infer_type(&mut self, value: &Node) -> Type {
+        if value.is_untyped_literal() {
+            self.type_vars += 1;
+            Type::Infer(self.type_vars)
+        } else {
+            value.ty()
+        }
+    }
+
+    // only literals, operators and other constants can be worked out before the program runs
+    fn expect_constant(&self, node: &Node) -> Result<(), Error> {
+        match node {
+            Node::Primary(..) => Ok(()),
+            // there's nothing at an address before the program runs
+            Node::UnaryExpr { token, .. }
+                if matches!(token.kind, TokenKind::MUL | TokenKind::BIT_AND) =>
+            {
+                let name = match token.kind {
+                    TokenKind::MUL => "*",
+                    _ => "&",
+                };
+                Err(Error::ConstantError {
+                    name: name.to_string(),
+                    filepath: token.filepath.clone(),
+                    coord: token.coord,
+                })
+            }
+            Node::UnaryExpr { node, .. } | Node::Cast { node, .. } => self.expect_constant(node),
+            Node::BinaryExpr { left, right, .. } => {
+                self.expect_constant(left)?;
+                self.expect_constant(right)
+            }
+            Node::ArrayLiteral { elements, .. } => {
+                for element in elements {
+                    self.expect_constant(element)?;
+                }
+                Ok(())
+            }
+            Node::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.expect_constant(value)?;
+                }
+                Ok(())
+            }
+            Node::Variant { args, .. } => {
+                for arg in args {
+                    self.expect_constant(arg)?;
+                }
+                Ok(())
+            }
+            Node::Match { token, .. } => Err(Error::ConstantError {
+                name: "match".to_string(),
+                filepath: token.filepath.clone(),
+                coord: token.coord,
+            }),
+            Node::Index { token, .. } => Err(Error::ConstantError {
+                name: "indexing".to_string(),
+                filepath: token.filepath.clone(),
+                coord: token.coord,
+            }),
+            Node::Field { token, .. } => Err(Error::ConstantError {
+                name: "field access".to_string(),
+                filepath: token.filepath.clone(),
+                coord: token.coord,
+            }),
+            Node::Variable { token, .. } if self.lookup(token)?.binding == Binding::Constant => {
+                Ok(())
+            }
+            Node::Variable { token, .. } | Node::Call { token, .. } => Err(Error::ConstantError {
+                name: name_of(token),
+                filepath: token.filepath.clone(),
+                coord: token.coord,
+            }),
+            _ => panic!("statement inside an expression"),
+        }
+    }
+
+    // functions and globals all end up as symbols in the same assembly file, so their names
+    // can only be used once, and not for anything built in
+    fn expect_undefined(&self, name: &Token) -> Result<(), Error> {
+        let x = name_of(name);
+        let global = self
+            .scopes
+            .first()
+            .is_some_and(|scope| scope.contains_key(&x));
+        if global || self.functions.contains_key(&x) || Builtin::from_name(&x).is_some() {
+            return Err(Error::RedefinitionError {
+                name: x,
+                filepath: name.filepath.clone(),
+                coord: name.coord,
+            });
+        }
+        Ok(())
+    }
+
+    // the name of a struct or enum can't be taken already, even by a built in type, and
+    // neither can the names of its fields or variants
+    fn expect_new_type(&self, name: &Token, fields: &[&Token]) -> Result<(), Error> {
+        let x = name_of(name);
+        if self.structs.contains_key(&x)
+            || self.enums.contains_key(&x)
+            || Type::from_name(&x).is_some()
+        {
+            return Err(Error::RedefinitionError {
+                name: x,
+                filepath: name.filepath.clone(),
+                coord: name.coord,
+            });
+        }
+        for (i, field) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|y| name_of(y) == name_of(field)) {
+                return Err(Error::RedefinitionError {
+                    name: name_of(field),
+                    filepath: field.filepath.clone(),
+                    coord: field.coord,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Declares the layout of the struct or enum `name`, after the layouts of the ones inside
+    // it. `outer` are those waiting on this one, so finding it among them means it contains
+    // itself and would never end.
+    fn lay_out(&mut self, name: &str, outer: &mut Vec<String>) -> Result<(), Error> {
+        if self.laid_out.contains(name) {
+            return Ok(());
+        }
+        // every field of every variant of an enum, along with the variant it is in
+        let fields = match self.structs.get(name) {
+            Some(fields) => fields.clone(),
+            None => self.enums[name]
+                .iter()
+                .flat_map(|(variant, fields)| {
+                    fields.iter().map(move |ty| (variant.clone(), ty.clone()))
+                })
+                .collect(),
+        };
+        outer.push(name.to_string());
+        for (field, ty) in fields.iter() {
+            self.expect_declared(ty, field)?;
+            if let Type::Struct(inner) | Type::Enum(inner) = &element_type(ty) {
+                if outer.contains(inner) {
+                    return Err(Error::TypeError {
+                        found: format!("`{}` inside itself", inner),
+                        expected: "struct of a fixed size".to_string(),
+                        filepath: field.filepath.clone(),
+                        coord: field.coord,
+                    });
+                }
+                self.lay_out(inner, outer)?;
+            }
+            if self.layouts.checked_size(ty).is_none() {
+                return Err(too_big(ty, field));
+            }
+        }
+        outer.pop();
+
+        let last = fields.last().map(|(x, _)| x.clone());
+        match self.enums.get(name) {
+            Some(variants) => {
+                let variants = variants
+                    .iter()
+                    .map(|(x, fields)| (name_of(x), fields.clone()))
+                    .collect();
+                self.layouts.declare_enum(name, variants);
+            }
+            None => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(x, ty)| (name_of(&x), ty))
+                    .collect();
+                self.layouts.declare(name, fields);
+            }
+        }
+        // each field fits, but all of them together might not
+        let ty = match self.enums.contains_key(name) {
+            true => Type::Enum(name.to_string()),
+            false => Type::Struct(name.to_string()),
+        };
+        if let Some(field) = last.filter(|_| self.layouts.checked_size(&ty).is_none()) {
+            return Err(too_big(&ty, &field));
+        }
+        self.laid_out.insert(name.to_string());
+        Ok(())
+    }
+
+    // a type written out in the program can only name structs that have been declared. The
+    // parser only makes an enum type out of a name it has seen declared.
+    fn expect_declared(&self, ty: &Type, token: &Token) -> Result<(), Error> {
+        match element_type(ty) {
+            Type::Pointer(ty) | Type::Vec(ty) => self.expect_declared(&ty, token),
+            Type::Struct(x) if !self.structs.contains_key(&x) => Err(Error::UndeclaredNameError {
+                name: x.clone(),
+                filepath: token.filepath.clone(),
+                coord: token.coord,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // Fills in what the aliases and newtypes in a type written out in the program stand for,
+    // then makes sure it only names types that exist and isn't too big.
+    fn resolve_type(&self, ty: &mut Type, token: &Token) -> Result<(), Error> {
+        *ty = self.types.fill_in(ty);
+        self.expect_declared(ty, token)?;
+        self.expect_sized(ty, token)
+    }
+
+    // A value of type `ty` has to fit in `MAX_SIZE` bytes, and so does whatever a pointer or vec
+    // in it points at, since that is indexed into the same way.
+    fn expect_sized(&self, ty: &Type, token: &Token) -> Result<(), Error> {
+        if self.layouts.checked_size(ty).is_none() {
+            return Err(too_big(ty, token));
+        }
+        match element_type(ty) {
+            Type::Pointer(ty) | Type::Vec(ty) => self.expect_sized(&ty, token),
+            _ => Ok(()),
+        }
+    }
+
+    // the types of the fields of `Name::Variant`, where `token` is `Name`
+    fn variant_fields(&self, token: &Token, name: &Token) -> Result<Vec<Type>, Error> {
+        let x = name_of(token);
+        let variants = match self.enums.get(&x) {
+            Some(variants) => variants,
+            None => {
+                return Err(Error::UndeclaredNameError {
+                    name: x,
+                    filepath: token.filepath.clone(),
+                    coord: token.coord,
+                })
+            }
+        };
+        match variants.iter().find(|(y, _)| name_of(y) == name_of(name)) {
+            Some((_, fields)) => Ok(fields.clone()),
+            None => Err(Error::TypeError {
+                found: format!("`{}`", name_of(name)),
+                expected: format!("variant of `{}`", x),
+                filepath: name.filepath.clone(),
+                coord: name.coord,
+            }),
+        }
+    }
+
+    // Declares the names `pattern` binds, where `ty` is the type of what it is matched against.
+    // Whether the pattern can match that type at all is left to the type checker.
+    fn analyse_pattern(&mut self, pattern: &mut Pattern, ty: &Type) -> Result<(), Error> {
+        match pattern {
+            Pattern::Wildcard(..) | Pattern::Literal(..) | Pattern::Range { .. } => Ok(()),
+            Pattern::Binding(Node::Variable {
+                token,
+                offset,
+                ty: binding_ty,
+                ..
+            }) => {
+                // a name can only be bound once in the same pattern
+                let scope = self.scopes.last().expect("pattern outside of a scope");
+                if scope.contains_key(&name_of(token)) {
+                    return Err(Error::RedefinitionError {
+                        name: name_of(token),
+                        filepath: token.filepath.clone(),
+                        coord: token.coord,
+                    });
+                }
+                *offset = self.declare(token, ty.clone(), Binding::Immutable).offset;
+                *binding_ty = ty.clone();
+                Ok(())
+            }
+            Pattern::Binding(..) => panic!("binding that isn't a variable"),
+            Pattern::Variant {
+                token,
+                name,
+                fields,
+            } => {
+                let types = self.variant_fields(token, name)?;
+                if types.len() != fields.len() {
+                    return Err(Error::TypeError {
+                        found: format!("{} fields", fields.len()),
+                        expected: format!("{} fields", types.len()),
+                        filepath: name.filepath.clone(),
+                        coord: name.coord,
+                    });
+                }
+                for (field, ty) in fields.iter_mut().zip(types.iter()) {
+                    self.analyse_pattern(field, ty)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token, ty: Type, binding: Binding) -> Local {
+        let local = Local {
+            offset: self.allocate(&ty),
+            global: false,
+            ty,
+            binding,
+            name: name.clone(),
+        };
+        self.scopes
+            .last_mut()
+            .expect("declaration outside of a scope")
+            .insert(name_of(name), local.clone());
+        local
+    }
+
+    // reserves a slot big enough for `ty`, aligned to its alignment
+    fn allocate(&mut self, ty: &Type) -> i64 {
+        let size = self.layouts.size(ty).max(1);
+        let align = self.layouts.align(ty);
+        self.stack_size = (self.stack_size + size + align - 1) / align * align;
+        self.max_stack_size = self.max_stack_size.max(self.stack_size);
+        self.stack_size
+    }
+
+    fn lookup(&self, name: &Token) -> Result<Local, Error> {
+        let x = match &name.kind {
+            TokenKind::DECL_NAME(x) => x,
+            _ => panic!("lookup() called on a non name token"),
+        };
+        for scope in self.scopes.iter().rev() {
+            if let Some(local) = scope.get(x) {
+                return Ok(local.clone());
+            }
+        }
+        Err(Error::UndeclaredNameError {
+            name: x.clone(),
+            filepath: name.filepath.clone(),
+            coord: name.coord,
+        })
+    }
+}
+
+// where something that can be assigned to or have its address taken is
+enum Place<'a> {
+    // the variable it is, or is part of
+    Variable(&'a Token),
+    // what a pointer points at, or part of it, which includes the elements of a vec
+    Pointer,
+}
+
+fn place(node: &Node) -> Option<Place<'_>> {
+    match node {
+        Node::Variable { token, .. } => Some(Place::Variable(token)),
+        Node::UnaryExpr { token, .. } if token.kind == TokenKind::MUL => Some(Place::Pointer),
+        Node::Index { array, .. } if matches!(array.ty().repr(), Type::Vec(..)) => {
+            Some(Place::Pointer)
+        }
+        Node::Index { array, .. } => place(array),
+        Node::Field { node, .. } => place(node),
+        _ => None,
+    }
+}
+
+// what an array is an array of, all the way down, seeing through aliases
+fn element_type(ty: &Type) -> Type {
+    match ty.repr() {
+        Type::Array(elem, ..) => element_type(&elem),
+        ty => ty,
+    }
+}
+
+fn too_big(ty: &Type, token: &Token) -> Error {
+    Error::TypeError {
+        found: format!("`{}`", ty),
+        expected: format!("type of at most {} bytes", MAX_SIZE),
+        filepath: token.filepath.clone(),
+        coord: token.coord,
+    }
+}
+
+fn no_field_error(field: &Token, name: &str) -> Error {
+    Error::TypeError {
+        found: format!("`{}`", name_of(field)),
+        expected: format!("field of `{}`", name),
+        filepath: field.filepath.clone(),
+        coord: field.coord,
+    }
+}
+
+fn name_of(label: &Token) -> String {
+    match &label.kind {
+        TokenKind::DECL_NAME(x) => x.clone(),
+        _ => panic!("expected a name token"),
+    }
+}
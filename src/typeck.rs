@@ -0,0 +1,1118 @@
+use crate::ast::*;
+use crate::builtins::Builtin;
+use crate::consteval;
+use crate::error::*;
+use crate::exhaustiveness;
+use crate::layout::Layouts;
+use crate::lexer::*;
+use crate::types::Type;
+use std::collections::HashMap;
+
+// Checks that every expression is used at the type it has, once the semantic pass has given
+// each variable its type. Literals without a suffix and variables declared without an
+// annotation are given the type they are used at here, so the generator never has to guess.
+pub struct TypeChecker {
+    // parameter and return types of every function
+    functions: HashMap<String, (Vec<Type>, Type)>,
+    // return type of the function being checked
+    ret_ty: Type,
+    // what each `Type::Infer` has turned out to be so far
+    bindings: HashMap<usize, Type>,
+    // values of the top level `const`s
+    constants: HashMap<String, i64>,
+    // the layouts the semantic pass gave every struct and enum
+    layouts: Layouts,
+}
+
+impl TypeChecker {
+    pub fn new(layouts: Layouts) -> Self {
+        Self {
+            functions: HashMap::new(),
+            ret_ty: Type::Unit,
+            bindings: HashMap::new(),
+            constants: HashMap::new(),
+            layouts,
+        }
+    }
+
+    pub fn check(&mut self, ast: &mut Node) -> Result<(), Error> {
+        self.check_node(ast, None).map(|_| ())
+    }
+
+    // `expected` is the type the surrounding code wants, which is what an unsuffixed literal
+    // turns into
+    fn check_node(&mut self, node: &mut Node, expected: Option<&Type>) -> Result<Type, Error> {
+        match node {
+            Node::Primary(token) => {
+                let ty = match &mut token.kind {
+                    TokenKind::INT(x) => return Ok(check_literal(x, expected)),
+                    TokenKind::FLOAT(..) => Type::F64,
+                    TokenKind::STR(..) => Type::Str,
+                    TokenKind::CHAR(..) => Type::Char,
+                    TokenKind::KEYWORD(KeywordKind::TRUE | KeywordKind::FALSE) => Type::Bool,
+                    // `null` can be a pointer to anything, so it is whichever one is wanted
+                    TokenKind::KEYWORD(KeywordKind::NULL) => {
+                        return Ok(match expected {
+                            Some(ty) if self.resolve(ty).is_pointer() => ty.clone(),
+                            _ => Type::Pointer(Box::new(Type::Unit)),
+                        })
+                    }
+                    _ => return Ok(Type::Unit),
+                };
+                // like an integer, any other literal can be a newtype made from its type
+                Ok(match expected {
+                    Some(expected) if expected.repr() == ty => expected.clone(),
+                    _ => ty,
+                })
+            }
+            Node::Variable { ty, .. } => Ok(ty.clone()),
+            Node::UnaryExpr { token, node } => self.check_unary(token, node, expected),
+            Node::BinaryExpr { token, left, right } => {
+                self.check_binary(token, left, right, expected)
+            }
+            // like Rust, only a `u8` can become a `char`, and nothing can become a `bool`. A
+            // newtype can become what it was made from and back, and a pointer can become
+            // another pointer or an integer and back.
+            Node::Cast { node, ty, .. } => {
+                let found = self.check_node(node, None)?;
+                let from = self.resolve(&found);
+                let repr = from.repr();
+                let allowed = match ty.repr() {
+                    to if to == repr => true,
+                    // an integer that isn't known yet becomes a `u8`
+                    Type::Char => repr == Type::U8 || self.unify(&from, &Type::U8),
+                    Type::Pointer(..) => repr.is_pointer() || repr.is_integer(),
+                    to if to.is_integer() => {
+                        repr.is_number()
+                            || matches!(repr, Type::Bool | Type::Char | Type::Pointer(..))
+                    }
+                    to => to.is_number() && repr.is_number(),
+                };
+                if !allowed {
+                    let token = node.token().expect("cast of a node without a token");
+                    return Err(Error::CastError {
+                        from: format!("{}", from),
+                        to: format!("{}", ty),
+                        filepath: token.filepath.clone(),
+                        coord: token.coord,
+                    });
+                }
+                Ok(ty.clone())
+            }
+            Node::Call {
+                token, args, ty, ..
+            } => {
+                let name = match &token.kind {
+                    TokenKind::DECL_NAME(x) => x,
+                    _ => panic!("call with a non name token"),
+                };
+                if let Some(builtin) = Builtin::from_name(name) {
+                    // a `vec()` holds whatever the vec it is used as holds
+                    if builtin == Builtin::Vec {
+                        match expected.map(|x| self.resolve(x)) {
+                            Some(x) if matches!(x.repr(), Type::Vec(elem) if *elem != Type::Unit) => {
+                                *ty = x
+                            }
+                            _ => {
+                                return Err(Error::TypeError {
+                                    found: "`vec()` of an unknown type".to_string(),
+                                    expected: "type annotation like `[i64]`".to_string(),
+                                    filepath: token.filepath.clone(),
+                                    coord: token.coord,
+                                })
+                            }
+                        }
+                    }
+                    self.check_builtin(builtin, args)?;
+                    return Ok(ty.clone());
+                }
+                let params = self.functions[name].0.clone();
+                for (arg, param) in args.iter_mut().zip(params.iter()) {
+                    let found = self.check_node(arg, Some(param))?;
+                    self.expect_type(&found, param, arg)?;
+                }
+                Ok(ty.clone())
+            }
+            Node::LetDecl { ty, value, .. } => {
+                let ty = ty
+                    .clone()
+                    .expect("let without a type after semantic analysis");
+                let found = self.check_node(value, Some(&ty))?;
+                self.expect_type(&found, &ty, value)?;
+                Ok(Type::Unit)
+            }
+            Node::CompoundExpr { nodes } => {
+                for node in nodes {
+                    self.check_node(node, None)?;
+                }
+                Ok(Type::Unit)
+            }
+            Node::Program { nodes } => {
+                for node in nodes.iter() {
+                    if let Node::FnDecl {
+                        name,
+                        params,
+                        ret_ty,
+                        ..
+                    } = node
+                    {
+                        if let TokenKind::DECL_NAME(x) = &name.kind {
+                            let params = params.iter().map(|x| x.ty()).collect();
+                            self.functions.insert(x.clone(), (params, ret_ty.clone()));
+                        }
+                    }
+                }
+                for node in nodes {
+                    self.check_node(node, None)?;
+                }
+                Ok(Type::Unit)
+            }
+            Node::FnDecl {
+                name, ret_ty, body, ..
+            } => {
+                self.ret_ty = ret_ty.clone();
+                self.check_node(body, None)?;
+                // everything in the body has been seen, so whatever is still unknown can be
+                // settled
+                self.finish_node(body)?;
+                // only functions returning nothing are allowed to fall off the end
+                if *ret_ty != Type::Unit && !always_returns(body) {
+                    return Err(Error::TypeError {
+                        found: format!("{}", Type::Unit),
+                        expected: format!("{}", ret_ty),
+                        filepath: name.filepath.clone(),
+                        coord: name.coord,
+                    });
+                }
+                Ok(Type::Unit)
+            }
+            Node::IfStmt {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                let found = self.check_node(condition, Some(&Type::Bool))?;
+                self.expect_type(&found, &Type::Bool, condition)?;
+                self.check_node(then, None)?;
+                if let Some(otherwise) = otherwise {
+                    self.check_node(otherwise, None)?;
+                }
+                Ok(Type::Unit)
+            }
+            Node::WhileStmt {
+                condition, body, ..
+            } => {
+                if let Some(condition) = condition {
+                    let found = self.check_node(condition, Some(&Type::Bool))?;
+                    self.expect_type(&found, &Type::Bool, condition)?;
+                }
+                self.check_node(body, None)?;
+                Ok(Type::Unit)
+            }
+            Node::ForStmt {
+                variable,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                let ty = variable.ty();
+                for bound in [start, end] {
+                    let found = self.check_node(bound, Some(&ty))?;
+                    expect_integer(&found, bound)?;
+                    self.expect_type(&found, &ty, bound)?;
+                }
+                self.check_node(body, None)?;
+                Ok(Type::Unit)
+            }
+            Node::ForEach { iterable, body, .. } => {
+                let found = self.check_node(iterable, None)?;
+                if !matches!(self.resolve(&found).repr(), Type::Array(..) | Type::Vec(..)) {
+                    return Err(type_error(
+                        format!("{}", self.resolve(&found)),
+                        "array or vec".to_string(),
+                        iterable,
+                    ));
+                }
+                self.check_node(body, None)?;
+                Ok(Type::Unit)
+            }
+            Node::LoopControl { .. } => Ok(Type::Unit),
+            Node::GlobalDecl {
+                token,
+                name,
+                ty,
+                value,
+                ..
+            } => {
+                let found = self.check_node(value, Some(ty))?;
+                self.expect_type(&found, ty, value)?;
+                self.finish_node(value)?;
+                self.evaluate_global(value, ty)?;
+                // only single values can be used in other constant expressions
+                if token.kind == TokenKind::KEYWORD(KeywordKind::CONST)
+                    && !ty.is_aggregate()
+                    && ty.repr() != Type::Str
+                {
+                    if let TokenKind::DECL_NAME(x) = &name.kind {
+                        let result = consteval::evaluate(value, &self.constants)?;
+                        self.constants.insert(x.clone(), result);
+                    }
+                }
+                Ok(Type::Unit)
+            }
+            Node::ArrayLiteral {
+                elements, ty: own, ..
+            } => {
+                let elem = match own {
+                    Type::Array(elem, ..) => elem.as_ref().clone(),
+                    _ => panic!("array literal without an array type"),
+                };
+                let hint = match expected.map(|x| self.resolve(x).repr()) {
+                    Some(Type::Array(hint, ..)) => *hint,
+                    _ => elem.clone(),
+                };
+                for element in elements {
+                    let found = self.check_node(element, Some(&hint))?;
+                    self.expect_type(&found, &elem, element)?;
+                }
+                Ok(own.clone())
+            }
+            Node::StructLiteral { fields, ty, .. } => {
+                let layout = match ty {
+                    Type::Struct(x) => self.layouts.of(x),
+                    _ => panic!("struct literal without a struct type"),
+                };
+                for (field, value) in fields {
+                    let expected = match &field.kind {
+                        TokenKind::DECL_NAME(x) => layout.field(x),
+                        _ => None,
+                    }
+                    .expect("struct literal with a field that was never declared")
+                    .ty
+                    .clone();
+                    let found = self.check_node(value, Some(&expected))?;
+                    self.expect_type(&found, &expected, value)?;
+                }
+                Ok(ty.clone())
+            }
+            Node::Field { node, ty, .. } => {
+                let found = self.check_node(node, None)?;
+                if !matches!(found.repr(), Type::Struct(..)) {
+                    return Err(type_error(
+                        format!("{}", self.resolve(&found)),
+                        "struct".to_string(),
+                        node,
+                    ));
+                }
+                Ok(ty.clone())
+            }
+            Node::Variant { name, args, ty, .. } => {
+                let layout = match ty {
+                    Type::Enum(x) => self.layouts.of_enum(x),
+                    _ => panic!("variant without an enum type"),
+                };
+                let (_, variant) = match &name.kind {
+                    TokenKind::DECL_NAME(x) => layout.variant(x),
+                    _ => None,
+                }
+                .expect("variant that was never declared");
+                for (arg, field) in args.iter_mut().zip(variant.fields.iter()) {
+                    let found = self.check_node(arg, Some(&field.ty))?;
+                    self.expect_type(&found, &field.ty, arg)?;
+                }
+                Ok(ty.clone())
+            }
+            // every arm has to have the same type, apart from a block that returns or leaves a
+            // loop, which never finishes so has no value to speak of
+            Node::Match { node, arms, ty, .. } => {
+                let matched = self.check_node(node, None)?;
+                // an arm that is a bare literal takes on the type the match is used at
+                if let Some(expected) = expected {
+                    let (own, expected) = (self.resolve(ty), self.resolve(expected));
+                    if matches!(own, Type::Infer(..)) {
+                        self.unify(&own, &expected);
+                    }
+                }
+                for (pattern, value) in arms.iter_mut() {
+                    self.check_pattern(pattern, &matched)?;
+                    let found = self.check_node(value, Some(ty))?;
+                    if let Node::CompoundExpr { .. } = value {
+                        if diverges(value) {
+                            continue;
+                        }
+                    }
+                    let found = self.resolve(&found);
+                    let expected = self.resolve(ty);
+                    if !self.unify(&found, &expected) {
+                        let token = value.token().unwrap_or(pattern.token());
+                        return Err(Error::TypeError {
+                            found: format!("{}", found),
+                            expected: format!("{}", expected),
+                            filepath: token.filepath.clone(),
+                            coord: token.coord,
+                        });
+                    }
+                }
+                Ok(ty.clone())
+            }
+            Node::StructDecl { .. } | Node::EnumDecl { .. } | Node::TypeDecl { .. } => {
+                Ok(Type::Unit)
+            }
+            Node::Index {
+                array, index, ty, ..
+            } => {
+                let found = self.check_node(array, None)?;
+                if !matches!(self.resolve(&found).repr(), Type::Array(..) | Type::Vec(..)) {
+                    return Err(type_error(
+                        format!("{}", self.resolve(&found)),
+                        "array or vec".to_string(),
+                        array,
+                    ));
+                }
+                let found = self.check_node(index, None)?;
+                expect_integer(&found, index)?;
+                Ok(ty.clone())
+            }
+        }
+    }
+
+    // A pattern can only be a literal or range of the type it is matched against, or a variant
+    // of it if it's an enum. A name takes on that type.
+    fn check_pattern(&mut self, pattern: &mut Pattern, ty: &Type) -> Result<(), Error> {
+        let resolved = self.resolve(ty);
+        let token = pattern.token().clone();
+        let (allowed, expected) = match pattern {
+            Pattern::Wildcard(..) => return Ok(()),
+            Pattern::Binding(node) => return self.expect_type(&node.ty(), ty, node),
+            Pattern::Literal(node) => {
+                let found = self.check_node(node, Some(ty))?;
+                self.expect_type(&found, ty, node)?;
+                (
+                    resolved.is_integer() || matches!(resolved.repr(), Type::Bool | Type::Char),
+                    "integer, char or bool",
+                )
+            }
+            Pattern::Range { start, end, .. } => {
+                for node in [start, end] {
+                    let found = self.check_node(node, Some(ty))?;
+                    self.expect_type(&found, ty, node)?;
+                }
+                (
+                    resolved.is_integer() || resolved.repr() == Type::Char,
+                    "integer or char",
+                )
+            }
+            Pattern::Variant { fields, name, .. } => {
+                let enum_name = match &token.kind {
+                    TokenKind::DECL_NAME(x) => x.clone(),
+                    _ => panic!("variant pattern without a name"),
+                };
+                if resolved.repr() != Type::Enum(enum_name.clone()) {
+                    return Err(Error::TypeError {
+                        found: enum_name,
+                        expected: format!("{}", resolved),
+                        filepath: token.filepath.clone(),
+                        coord: token.coord,
+                    });
+                }
+                let layout = self.layouts.of_enum(&enum_name);
+                let (_, variant) = match &name.kind {
+                    TokenKind::DECL_NAME(x) => layout.variant(x),
+                    _ => None,
+                }
+                .expect("variant pattern that was never declared");
+                for (field, declared) in fields.iter_mut().zip(variant.fields.iter()) {
+                    self.check_pattern(field, &declared.ty)?;
+                }
+                return Ok(());
+            }
+        };
+        if !allowed {
+            return Err(Error::TypeError {
+                found: format!("{}", resolved),
+                expected: expected.to_string(),
+                filepath: token.filepath.clone(),
+                coord: token.coord,
+            });
+        }
+        Ok(())
+    }
+
+    fn finish_pattern(&mut self, pattern: &mut Pattern) -> Result<(), Error> {
+        match pattern {
+            Pattern::Wildcard(..) => Ok(()),
+            Pattern::Binding(node) | Pattern::Literal(node) => self.finish_node(node),
+            Pattern::Range { start, end, .. } => {
+                self.finish_node(start)?;
+                self.finish_node(end)
+            }
+            Pattern::Variant { fields, .. } => {
+                for field in fields {
+                    self.finish_pattern(field)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // The generator only ever sees the values global initialisers work out to, so each is
+    // replaced with the literal it comes to. There's no working anything out with text or
+    // arrays, so those have to be written out as literals to begin with.
+    fn evaluate_global(&self, value: &mut Node, ty: &Type) -> Result<(), Error> {
+        let token = value.token().expect("initialiser without a token").clone();
+        match (&ty.repr(), &mut *value) {
+            (Type::Str, Node::Primary(..)) => return Ok(()),
+            (Type::Array(elem, ..), Node::ArrayLiteral { elements, .. }) => {
+                for element in elements {
+                    self.evaluate_global(element, elem)?;
+                }
+                return Ok(());
+            }
+            (Type::Struct(name), Node::StructLiteral { fields, .. }) => {
+                let layout = self.layouts.of(name);
+                for (field, value) in fields {
+                    let field = match &field.kind {
+                        TokenKind::DECL_NAME(x) => layout.field(x),
+                        _ => None,
+                    }
+                    .expect("struct literal with a field that was never declared");
+                    self.evaluate_global(value, &field.ty)?;
+                }
+                return Ok(());
+            }
+            (
+                Type::Enum(name),
+                Node::Variant {
+                    name: variant,
+                    args,
+                    ..
+                },
+            ) => {
+                let layout = self.layouts.of_enum(name);
+                let (_, variant) = match &variant.kind {
+                    TokenKind::DECL_NAME(x) => layout.variant(x),
+                    _ => None,
+                }
+                .expect("variant that was never declared");
+                for (arg, field) in args.iter_mut().zip(variant.fields.iter()) {
+                    self.evaluate_global(arg, &field.ty)?;
+                }
+                return Ok(());
+            }
+            (repr @ (Type::Str | Type::Array(..) | Type::Struct(..) | Type::Enum(..)), _) => {
+                let message = match repr {
+                    Type::Str => "a global `str` has to be a string literal",
+                    Type::Array(..) => "a global array has to be an array literal",
+                    Type::Struct(..) => "a global struct has to be a struct literal",
+                    _ => "a global enum has to be one of its variants",
+                };
+                return Err(Error::EvaluationError {
+                    message: message.to_string(),
+                    filepath: token.filepath,
+                    coord: token.coord,
+                });
+            }
+            _ => (),
+        }
+
+        let result = consteval::evaluate(value, &self.constants)?;
+        let kind = match ty.repr() {
+            Type::F64 => TokenKind::FLOAT(f64::from_bits(result as u64)),
+            Type::Bool if result == 0 => TokenKind::KEYWORD(KeywordKind::FALSE),
+            Type::Bool => TokenKind::KEYWORD(KeywordKind::TRUE),
+            Type::Char => TokenKind::CHAR(
+                char::from_u32(result as u32).expect("invalid char from a constant"),
+            ),
+            _ => TokenKind::INT(IntLiteral {
+                value: result as u64,
+                ty: Some(ty.clone()),
+            }),
+        };
+        *value = Node::Primary(Token { kind, ..token });
+        Ok(())
+    }
+
+    fn check_builtin(&mut self, builtin: Builtin, args: &mut [Node]) -> Result<(), Error> {
+        match builtin {
+            // anything but `()`, arrays and vecs can be printed
+            Builtin::Print | Builtin::Println | Builtin::Eprint | Builtin::Eprintln => {
+                for arg in args {
+                    let found = self.check_node(arg, None)?;
+                    if found == Type::Unit
+                        || found.is_aggregate()
+                        || matches!(found.repr(), Type::Vec(..))
+                    {
+                        return Err(type_error(
+                            format!("{}", self.resolve(&found)),
+                            "number, bool, char or str".to_string(),
+                            arg,
+                        ));
+                    }
+                }
+            }
+            // the length of a `str` or vec is found at runtime, and of an array from its type
+            Builtin::Len => {
+                let found = self.check_node(&mut args[0], None)?;
+                if !matches!(
+                    self.resolve(&found).repr(),
+                    Type::Str | Type::Array(..) | Type::Vec(..)
+                ) {
+                    return Err(type_error(
+                        format!("{}", self.resolve(&found)),
+                        "str, array or vec".to_string(),
+                        &args[0],
+                    ));
+                }
+            }
+            // any pointer can be freed, `null` included, and so can a vec
+            Builtin::Free => {
+                let found = self.check_node(&mut args[0], None)?;
+                if !matches!(
+                    self.resolve(&found).repr(),
+                    Type::Pointer(..) | Type::Vec(..)
+                ) {
+                    return Err(type_error(
+                        format!("{}", self.resolve(&found)),
+                        "pointer or vec".to_string(),
+                        &args[0],
+                    ));
+                }
+            }
+            Builtin::Push => {
+                let found = self.check_node(&mut args[0], None)?;
+                let elem = expect_vec(&self.resolve(&found), &args[0])?;
+                let value = self.check_node(&mut args[1], Some(&elem))?;
+                self.expect_type(&value, &elem, &args[1])?;
+            }
+            Builtin::Pop => {
+                let found = self.check_node(&mut args[0], None)?;
+                expect_vec(&self.resolve(&found), &args[0])?;
+            }
+            _ => {
+                for (arg, param) in args.iter_mut().zip(builtin.params().iter()) {
+                    let found = self.check_node(arg, Some(param))?;
+                    self.expect_type(&found, param, arg)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_unary(
+        &mut self,
+        token: &Token,
+        node: &mut Node,
+        expected: Option<&Type>,
+    ) -> Result<Type, Error> {
+        match token.kind {
+            TokenKind::KEYWORD(KeywordKind::RETURN) => {
+                let ret_ty = self.ret_ty.clone();
+                let found = self.check_node(node, Some(&ret_ty))?;
+                match node.token() {
+                    Some(..) => self.expect_type(&found, &ret_ty, node)?,
+                    None => self.expect_type(&found, &ret_ty, &Node::Primary(token.clone()))?,
+                }
+                Ok(Type::Unit)
+            }
+            // whether a minus is applied to a signed type is checked once all types are known
+            TokenKind::PLUS | TokenKind::MINUS => {
+                let ty = self.check_node(node, expected)?;
+                expect_number(&ty, node)?;
+                Ok(ty)
+            }
+            TokenKind::BIT_NOT => {
+                let ty = self.check_node(node, expected)?;
+                expect_integer(&ty, node)?;
+                Ok(ty)
+            }
+            TokenKind::NOT => {
+                let ty = self.check_node(node, Some(&Type::Bool))?;
+                self.expect_type(&ty, &Type::Bool, node)?;
+                Ok(Type::Bool)
+            }
+            TokenKind::MUL => {
+                let ty = self.check_node(node, None)?;
+                expect_pointer(&self.resolve(&ty), node)
+            }
+            // the semantic pass has made sure it is something with an address
+            TokenKind::BIT_AND => {
+                let ty = self.check_node(node, None)?;
+                Ok(Type::Pointer(Box::new(ty)))
+            }
+            _ => panic!("unimplemented unary operator"),
+        }
+    }
+
+    fn check_binary(
+        &mut self,
+        token: &Token,
+        left: &mut Node,
+        right: &mut Node,
+        expected: Option<&Type>,
+    ) -> Result<Type, Error> {
+        match token.kind {
+            TokenKind::EQUALS => {
+                let ty = self.check_node(left, None)?;
+                let found = self.check_node(right, Some(&ty))?;
+                self.expect_type(&found, &ty, right)?;
+                Ok(ty)
+            }
+            TokenKind::AND | TokenKind::OR => {
+                for side in [left, right] {
+                    let found = self.check_node(side, Some(&Type::Bool))?;
+                    self.expect_type(&found, &Type::Bool, side)?;
+                }
+                Ok(Type::Bool)
+            }
+            TokenKind::DOUBLE_EQUALS
+            | TokenKind::NOT_EQUALS
+            | TokenKind::LESS_THAN
+            | TokenKind::LESS_EQUALS
+            | TokenKind::GREATER_THAN
+            | TokenKind::GREATER_EQUALS => {
+                self.check_operands(token, left, right, None)?;
+                Ok(Type::Bool)
+            }
+            // a pointer moves by any integer number of elements, and taking one pointer from
+            // another gives how many elements apart they are
+            TokenKind::PLUS | TokenKind::MINUS if left.ty().is_pointer() => {
+                let ty = self.check_node(left, None)?;
+                expect_pointer(&self.resolve(&ty), left)?;
+                if token.kind == TokenKind::MINUS && right.ty().is_pointer() {
+                    let found = self.check_node(right, Some(&ty))?;
+                    self.expect_type(&found, &ty, right)?;
+                    return Ok(Type::I64);
+                }
+                let found = self.check_node(right, None)?;
+                expect_integer(&found, right)?;
+                Ok(ty)
+            }
+            // the shift amount can be any integer type
+            TokenKind::SHIFT_LEFT | TokenKind::SHIFT_RIGHT | TokenKind::UNSIGNED_SHIFT_RIGHT => {
+                let ty = self.check_node(left, expected)?;
+                expect_integer(&ty, left)?;
+                let amount = self.check_node(right, None)?;
+                expect_integer(&amount, right)?;
+                Ok(ty)
+            }
+            _ => self.check_operands(token, left, right, expected),
+        }
+    }
+
+    // both sides of an operator have to be the same type, and a literal on either side takes on
+    // the type of the other. Arithmetic works on numbers and the bitwise operators only on
+    // integers, while `char`s and pointers can also be compared and `bool`s checked for
+    // equality.
+    fn check_operands(
+        &mut self,
+        token: &Token,
+        left: &mut Node,
+        right: &mut Node,
+        expected: Option<&Type>,
+    ) -> Result<Type, Error> {
+        let untyped = left.is_untyped_literal() || left.is_null();
+        let (left_ty, right_ty) = if untyped && !right.is_untyped_literal() {
+            let right_ty = self.check_node(right, expected)?;
+            (self.check_node(left, Some(&right_ty))?, right_ty)
+        } else {
+            let left_ty = self.check_node(left, expected)?;
+            (left_ty.clone(), self.check_node(right, Some(&left_ty))?)
+        };
+        let (allowed, expected) = match token.kind {
+            TokenKind::BIT_AND | TokenKind::BIT_OR | TokenKind::BIT_XOR => {
+                (left_ty.is_integer(), "integer")
+            }
+            TokenKind::DOUBLE_EQUALS | TokenKind::NOT_EQUALS => (
+                left_ty.is_number()
+                    || matches!(left_ty.repr(), Type::Bool | Type::Char | Type::Pointer(..)),
+                "number, bool, char or pointer",
+            ),
+            TokenKind::LESS_THAN
+            | TokenKind::LESS_EQUALS
+            | TokenKind::GREATER_THAN
+            | TokenKind::GREATER_EQUALS => (
+                left_ty.is_number() || matches!(left_ty.repr(), Type::Char | Type::Pointer(..)),
+                "number, char or pointer",
+            ),
+            _ => (left_ty.is_number(), "integer or f64"),
+        };
+        if !allowed {
+            return Err(type_error(
+                format!("{}", left_ty),
+                expected.to_string(),
+                left,
+            ));
+        }
+        self.expect_type(&right_ty, &left_ty, right)?;
+        Ok(left_ty)
+    }
+
+    // `found` and `expected` have to be the same type, and an integer that isn't known yet
+    // becomes whatever it is compared against
+    fn expect_type(&mut self, found: &Type, expected: &Type, node: &Node) -> Result<(), Error> {
+        let found = self.resolve(found);
+        let expected = self.resolve(expected);
+        if self.unify(&found, &expected) {
+            return Ok(());
+        }
+        Err(type_error(
+            format!("{}", found),
+            format!("{}", expected),
+            node,
+        ))
+    }
+
+    // whether `a` and `b` can be the same type, binding any integers that aren't known yet to
+    // make them so. Both have to have been resolved.
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        match (a.expand(), b.expand()) {
+            _ if a == b => true,
+            // bound to the type as it was written, so errors still call it by that name
+            (Type::Infer(n), _) if b.is_integer() => {
+                self.bindings.insert(n, b.clone());
+                true
+            }
+            (_, Type::Infer(n)) if a.is_integer() => {
+                self.bindings.insert(n, a.clone());
+                true
+            }
+            (Type::Array(a, n), Type::Array(b, m)) => n == m && self.unify(&a, &b),
+            (Type::Pointer(a), Type::Pointer(b)) | (Type::Vec(a), Type::Vec(b)) => {
+                self.unify(&a, &b)
+            }
+            _ => false,
+        }
+    }
+
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Infer(n) => match self.bindings.get(n) {
+                Some(x) => self.resolve(x),
+                None => ty.clone(),
+            },
+            Type::Array(elem, len) => Type::Array(Box::new(self.resolve(elem)), *len),
+            Type::Pointer(ty) => Type::Pointer(Box::new(self.resolve(ty))),
+            Type::Vec(elem) => Type::Vec(Box::new(self.resolve(elem))),
+            _ => ty.clone(),
+        }
+    }
+
+    // like Rust, an integer that nothing pins down is an `i64`
+    fn concrete(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Type::Infer(..) => Type::I64,
+            Type::Array(elem, len) => Type::Array(Box::new(self.concrete(&elem)), len),
+            Type::Pointer(ty) => Type::Pointer(Box::new(self.concrete(&ty))),
+            Type::Vec(elem) => Type::Vec(Box::new(self.concrete(&elem))),
+            ty => ty,
+        }
+    }
+
+    // writes the worked out types back into the tree, now that literals know their types and
+    // can be checked against their range
+    fn finish_node(&mut self, node: &mut Node) -> Result<(), Error> {
+        match node {
+            Node::Primary(token) => self.finish_literal(token, false),
+            Node::Variable { ty, .. } => {
+                *ty = self.concrete(ty);
+                Ok(())
+            }
+            Node::UnaryExpr { token, node } => {
+                match (&token.kind, node.as_mut()) {
+                    // a minus in front of a literal is part of the literal as far as its range
+                    // goes, so `-128i8` fits
+                    (TokenKind::MINUS, Node::Primary(token)) => self.finish_literal(token, true)?,
+                    (_, node) => self.finish_node(node)?,
+                }
+                if token.kind == TokenKind::MINUS {
+                    expect_signed(&node.ty(), node)?;
+                }
+                Ok(())
+            }
+            Node::BinaryExpr { left, right, .. } => {
+                self.finish_node(left)?;
+                self.finish_node(right)
+            }
+            Node::Cast { node, .. } => self.finish_node(node),
+            Node::ArrayLiteral { elements, ty, .. } => {
+                *ty = self.concrete(ty);
+                for element in elements {
+                    self.finish_node(element)?;
+                }
+                Ok(())
+            }
+            Node::Index {
+                array, index, ty, ..
+            } => {
+                *ty = self.concrete(ty);
+                self.finish_node(array)?;
+                self.finish_node(index)
+            }
+            Node::StructLiteral { fields, .. } => {
+                for (_, value) in fields {
+                    self.finish_node(value)?;
+                }
+                Ok(())
+            }
+            Node::Field { node, .. } => self.finish_node(node),
+            Node::Variant { args, .. } => {
+                for arg in args {
+                    self.finish_node(arg)?;
+                }
+                Ok(())
+            }
+            // the values patterns compare against are only known once their literals are
+            // finished, so this is where the arms are checked for covering everything
+            Node::Match {
+                token,
+                node,
+                arms,
+                ty,
+                ..
+            } => {
+                *ty = self.concrete(ty);
+                self.finish_node(node)?;
+                for (pattern, value) in arms.iter_mut() {
+                    self.finish_pattern(pattern)?;
+                    self.finish_node(value)?;
+                }
+                let patterns: Vec<&Pattern> = arms.iter().map(|(x, _)| x).collect();
+                exhaustiveness::check(&patterns, &node.ty(), token, &self.layouts)
+            }
+            Node::Call { args, .. } => {
+                for arg in args {
+                    self.finish_node(arg)?;
+                }
+                Ok(())
+            }
+            Node::LetDecl { ty, value, .. } => {
+                *ty = ty.as_ref().map(|x| self.concrete(x));
+                self.finish_node(value)
+            }
+            Node::CompoundExpr { nodes } | Node::Program { nodes } => {
+                for node in nodes {
+                    self.finish_node(node)?;
+                }
+                Ok(())
+            }
+            Node::IfStmt {
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.finish_node(condition)?;
+                self.finish_node(then)?;
+                match otherwise {
+                    Some(otherwise) => self.finish_node(otherwise),
+                    None => Ok(()),
+                }
+            }
+            Node::WhileStmt {
+                condition, body, ..
+            } => {
+                if let Some(condition) = condition {
+                    self.finish_node(condition)?;
+                }
+                self.finish_node(body)
+            }
+            Node::ForStmt {
+                variable,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.finish_node(variable)?;
+                self.finish_node(start)?;
+                self.finish_node(end)?;
+                self.finish_node(body)
+            }
+            Node::ForEach {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.finish_node(variable)?;
+                self.finish_node(iterable)?;
+                self.finish_node(body)
+            }
+            Node::FnDecl { .. }
+            | Node::LoopControl { .. }
+            | Node::GlobalDecl { .. }
+            | Node::StructDecl { .. }
+            | Node::EnumDecl { .. }
+            | Node::TypeDecl { .. } => Ok(()),
+        }
+    }
+
+    fn finish_literal(&mut self, token: &mut Token, negated: bool) -> Result<(), Error> {
+        let literal = match &mut token.kind {
+            TokenKind::INT(x) => x,
+            _ => return Ok(()),
+        };
+        let ty = self.concrete(&literal.ty());
+        literal.ty = Some(ty.clone());
+
+        let (min, max) = ty.int_range();
+        let value = if negated {
+            -(literal.value as i128)
+        } else {
+            literal.value as i128
+        };
+        if value < min || value > max {
+            return Err(Error::TypeError {
+                found: format!("{}", value),
+                expected: format!("{} in range {}..={}", ty, min, max),
+                filepath: token.filepath.clone(),
+                coord: token.coord,
+            });
+        }
+        Ok(())
+    }
+}
+
+// gives an integer literal without a suffix the type it is used at. Whether its value fits is
+// checked in `finish_literal`, once that type is known for sure.
+fn check_literal(literal: &mut IntLiteral, expected: Option<&Type>) -> Type {
+    let ty = match (&literal.ty, expected) {
+        (Some(ty), _) => ty.clone(),
+        (None, Some(ty)) if ty.is_integer() => ty.clone(),
+        (None, _) => Type::I64,
+    };
+    literal.ty = Some(ty.clone());
+    ty
+}
+
+fn expect_integer(found: &Type, node: &Node) -> Result<(), Error> {
+    if found.is_integer() {
+        return Ok(());
+    }
+    Err(type_error(
+        format!("{}", found),
+        "integer".to_string(),
+        node,
+    ))
+}
+
+fn expect_number(found: &Type, node: &Node) -> Result<(), Error> {
+    if found.is_number() {
+        return Ok(());
+    }
+    Err(type_error(
+        format!("{}", found),
+        "integer or f64".to_string(),
+        node,
+    ))
+}
+
+// the type `found` points at, which `null` on its own doesn't have
+fn expect_pointer(found: &Type, node: &Node) -> Result<Type, Error> {
+    match found.repr() {
+        Type::Pointer(ty) if *ty != Type::Unit => Ok(*ty),
+        _ => Err(type_error(
+            format!("{}", found),
+            "pointer to a value".to_string(),
+            node,
+        )),
+    }
+}
+
+// the type `found` holds, if it is a vec
+fn expect_vec(found: &Type, node: &Node) -> Result<Type, Error> {
+    match found.repr() {
+        Type::Vec(elem) => Ok(*elem),
+        _ => Err(type_error(format!("{}", found), "vec".to_string(), node)),
+    }
+}
+
+fn expect_signed(found: &Type, node: &Node) -> Result<(), Error> {
+    if found.is_float() || found.is_integer() && found.is_signed() {
+        return Ok(());
+    }
+    Err(type_error(
+        format!("{}", found),
+        "signed integer or f64".to_string(),
+        node,
+    ))
+}
+
+fn type_error(found: String, expected: String, node: &Node) -> Error {
+    let token = node.token().expect("type error on a node without a token");
+    Error::TypeError {
+        found,
+        expected,
+        filepath: token.filepath.clone(),
+        coord: token.coord,
+    }
+}
+
+// whether every path through `node` ends in a `return`
+fn always_returns(node: &Node) -> bool {
+    match node {
+        Node::UnaryExpr { token, .. } => token.kind == TokenKind::KEYWORD(KeywordKind::RETURN),
+        Node::CompoundExpr { nodes } => nodes.iter().any(always_returns),
+        Node::IfStmt {
+            then,
+            otherwise: Some(otherwise),
+            ..
+        } => always_returns(then) && always_returns(otherwise),
+        // a `loop` can only be left through a `break`
+        Node::WhileStmt {
+            label,
+            condition: None,
+            body,
+            ..
+        } => !breaks_out(body, label, 0),
+        Node::Match { arms, .. } => arms.iter().all(|(_, x)| always_returns(x)),
+        _ => false,
+    }
+}
+
+// whether `node` never finishes, because every path through it either returns or jumps
+// somewhere else with a `break` or `continue`
+fn diverges(node: &Node) -> bool {
+    match node {
+        Node::LoopControl { .. } => true,
+        Node::CompoundExpr { nodes } => nodes.iter().any(diverges),
+        Node::IfStmt {
+            then,
+            otherwise: Some(otherwise),
+            ..
+        } => diverges(then) && diverges(otherwise),
+        Node::Match { arms, .. } => arms.iter().all(|(_, x)| diverges(x)),
+        node => always_returns(node),
+    }
+}
+
+// whether `node` contains a `break` out of the loop labelled `label`, `depth` being how many
+// loops inside that one we are
+fn breaks_out(node: &Node, label: &Option<Token>, depth: usize) -> bool {
+    match node {
+        Node::LoopControl {
+            token,
+            label: target,
+        } if token.kind == TokenKind::KEYWORD(KeywordKind::BREAK) => match (target, label) {
+            (None, _) => depth == 0,
+            (Some(target), Some(label)) => target.kind == label.kind,
+            (Some(..), None) => false,
+        },
+        Node::CompoundExpr { nodes } => nodes.iter().any(|x| breaks_out(x, label, depth)),
+        Node::IfStmt {
+            then, otherwise, ..
+        } => {
+            breaks_out(then, label, depth)
+                || otherwise
+                    .as_ref()
+                    .is_some_and(|x| breaks_out(x, label, depth))
+        }
+        Node::WhileStmt { body, .. } | Node::ForStmt { body, .. } | Node::ForEach { body, .. } => {
+            breaks_out(body, label, depth + 1)
+        }
+        Node::Match { arms, .. } => arms.iter().any(|(_, x)| breaks_out(x, label, depth)),
+        _ => false,
+    }
+}
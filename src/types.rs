@@ -0,0 +1,205 @@
+use std::fmt;
+
+// the most bytes a value can take up, so where it is kept can always be reached from %rbp or
+// %rip in the 32 bits an instruction has room for, and adding sizes up never overflows
+pub const MAX_SIZE: i64 = i32::MAX as i64;
+
+#[derive(Debug, Clone)]
+pub enum Type {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    // a double precision float, which is moved around in %rax as its bits like everything
+    // else and only goes into an xmm register to be worked with
+    F64,
+    // `true` or `false`, kept as 1 or 0 in a single byte
+    Bool,
+    // a unicode scalar value
+    Char,
+    // a fixed number of elements laid out one after the other
+    Array(Box<Type>, usize),
+    // a struct, by name, whose fields are found through `Layouts::of`
+    Struct(String),
+    // an enum, by name, whose variants are found through `Layouts::of_enum`
+    Enum(String),
+    // Another name for a type, by name, which is the same type as the one it stands for but is
+    // called by its own name in errors. The parser only knows the name, and what it stands for
+    // is filled in from the `TypeTable` by the semantic pass.
+    Alias(String, Option<Box<Type>>),
+    // a type of its own made from another, by name, which is kept the same way as that type
+    // and can be worked with the same way but can't be mixed with it. What it is made from is
+    // filled in the same way as for an alias.
+    Newtype(String, Option<Box<Type>>),
+    // the address of a value of the type it points at, which can be anywhere in memory
+    Pointer(Box<Type>),
+    // a growable array, kept as the address of a header on the heap holding the address of
+    // its elements, how many there are and how many there is room for. Copies of one share
+    // the same elements.
+    Vec(Box<Type>),
+    // a pointer to text in `.rodata`, ending with a zero byte
+    Str,
+    // what functions without a `-> type` return
+    Unit,
+    // an integer variable without an annotation, whose type the type checker works out from
+    // how it is used
+    Infer(usize),
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "i8" => Some(Type::I8),
+            "i16" => Some(Type::I16),
+            "i32" => Some(Type::I32),
+            "i64" => Some(Type::I64),
+            "u8" => Some(Type::U8),
+            "u16" => Some(Type::U16),
+            "u32" => Some(Type::U32),
+            "u64" => Some(Type::U64),
+            "f64" => Some(Type::F64),
+            "bool" => Some(Type::Bool),
+            "char" => Some(Type::Char),
+            "str" => Some(Type::Str),
+            _ => None,
+        }
+    }
+
+    // what an alias stands for, through as many aliases as it takes, and anything else as it is
+    pub fn expand(&self) -> Type {
+        match self {
+            Type::Alias(_, Some(target)) => target.expand(),
+            ty => ty.clone(),
+        }
+    }
+
+    // the type a value of this one is kept as, which is what a newtype was made from
+    pub fn repr(&self) -> Type {
+        match self.expand() {
+            Type::Newtype(_, Some(target)) => target.repr(),
+            ty => ty,
+        }
+    }
+
+    // Size in bytes of anything but a struct, enum or array, which are laid out by the semantic
+    // pass and found through `Layouts::size` instead.
+    pub fn scalar_size(&self) -> i64 {
+        match self.repr() {
+            Type::I8 | Type::U8 | Type::Bool => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::Char => 4,
+            // reserve enough room for whichever integer type it turns out to be
+            Type::I64
+            | Type::U64
+            | Type::F64
+            | Type::Pointer(..)
+            | Type::Vec(..)
+            | Type::Str
+            | Type::Infer(..) => 8,
+            Type::Unit => 0,
+            ty => panic!("size of aggregate {} without its layout", ty),
+        }
+    }
+
+    // whether it is made up of other values, so is worked with through its address rather than
+    // kept in %rax
+    pub fn is_aggregate(&self) -> bool {
+        matches!(
+            self.repr(),
+            Type::Array(..) | Type::Struct(..) | Type::Enum(..)
+        )
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self.repr(), Type::I8 | Type::I16 | Type::I32 | Type::I64)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self.repr(),
+            Type::I8
+                | Type::I16
+                | Type::I32
+                | Type::I64
+                | Type::U8
+                | Type::U16
+                | Type::U32
+                | Type::U64
+                | Type::Infer(..)
+        )
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self.repr(), Type::Pointer(..))
+    }
+
+    pub fn is_float(&self) -> bool {
+        self.repr() == Type::F64
+    }
+
+    // whether arithmetic works on it
+    pub fn is_number(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    // the range of values the type can hold, as (min, max)
+    pub fn int_range(&self) -> (i128, i128) {
+        let bits = self.scalar_size() * 8;
+        if self.is_signed() {
+            (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        } else {
+            (0, (1 << bits) - 1)
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::F64 => write!(f, "f64"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Array(elem, len) => write!(f, "[{}; {}]", elem, len),
+            Type::Pointer(ty) => write!(f, "*{}", ty),
+            Type::Vec(elem) => write!(f, "[{}]", elem),
+            Type::Struct(name)
+            | Type::Enum(name)
+            | Type::Alias(name, ..)
+            | Type::Newtype(name, ..) => write!(f, "{}", name),
+            Type::Str => write!(f, "str"),
+            Type::Unit => write!(f, "()"),
+            Type::Infer(..) => write!(f, "{{integer}}"),
+        }
+    }
+}
+
+// Two types are the same if they are once their aliases are expanded, so an alias can be used
+// anywhere the type it stands for can. A newtype is only ever the same as itself, and so is an
+// alias that hasn't had what it stands for filled in yet.
+impl PartialEq for Type {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.expand(), other.expand()) {
+            (Type::Array(a, n), Type::Array(b, m)) => n == m && a == b,
+            (Type::Pointer(a), Type::Pointer(b)) | (Type::Vec(a), Type::Vec(b)) => a == b,
+            (Type::Struct(a), Type::Struct(b))
+            | (Type::Enum(a), Type::Enum(b))
+            | (Type::Alias(a, ..), Type::Alias(b, ..))
+            | (Type::Newtype(a, ..), Type::Newtype(b, ..)) => a == b,
+            (Type::Infer(a), Type::Infer(b)) => a == b,
+            // the rest don't hold anything, so are the same if they are the same kind
+            (a, b) => std::mem::discriminant(&a) == std::mem::discriminant(&b),
+        }
+    }
+}
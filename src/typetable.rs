@@ -0,0 +1,175 @@
+use crate::error::Error;
+use crate::lexer::{KeywordKind, Token, TokenKind};
+use crate::types::Type;
+use std::collections::HashMap;
+
+// what a name declared as a type is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Struct,
+    Enum,
+    Alias,
+    Newtype,
+}
+
+// Every name the program declares as a type. The parser fills it in from the tokens before
+// parsing anything, so it knows what a name stands for even when it is used before it is
+// declared, and adds what each `type` and `newtype` stands for as it parses them. `resolve`
+// then makes sure those make sense, and the semantic pass fills in what they stand for in
+// every type written in the program with `fill_in`.
+pub struct TypeTable {
+    // every declaration in the order they are written, including ones of the same name
+    declared: Vec<(Token, Kind)>,
+    kinds: HashMap<String, Kind>,
+    // what each `type` and `newtype` stands for, as written
+    targets: Vec<(Token, Type)>,
+}
+
+impl TypeTable {
+    pub fn new(tokens: &[Token]) -> Self {
+        let declared: Vec<(Token, Kind)> = tokens
+            .windows(2)
+            .filter_map(|x| {
+                let kind = match (&x[0].kind, &x[1].kind) {
+                    (TokenKind::KEYWORD(keyword), TokenKind::DECL_NAME(..)) => match keyword {
+                        KeywordKind::STRUCT => Kind::Struct,
+                        KeywordKind::ENUM => Kind::Enum,
+                        KeywordKind::TYPE => Kind::Alias,
+                        KeywordKind::NEWTYPE => Kind::Newtype,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                Some((x[1].clone(), kind))
+            })
+            .collect();
+        let mut kinds = HashMap::new();
+        for (name, kind) in declared.iter() {
+            kinds.entry(name_of(name)).or_insert(*kind);
+        }
+        Self {
+            declared,
+            kinds,
+            targets: Vec::new(),
+        }
+    }
+
+    // the type a name written where a type goes stands for. A name that isn't declared is
+    // taken to be a struct, which the semantic pass reports if it never turns up.
+    pub fn type_of(&self, name: &str) -> Type {
+        if let Some(ty) = Type::from_name(name) {
+            return ty;
+        }
+        match self.kinds.get(name) {
+            Some(Kind::Enum) => Type::Enum(name.to_string()),
+            Some(Kind::Alias) => Type::Alias(name.to_string(), None),
+            Some(Kind::Newtype) => Type::Newtype(name.to_string(), None),
+            Some(Kind::Struct) | None => Type::Struct(name.to_string()),
+        }
+    }
+
+    pub fn define(&mut self, name: Token, ty: Type) {
+        self.targets.push((name, ty));
+    }
+
+    // Makes sure no two types have the same name, and that every alias and newtype stands
+    // for a type that exists without going round in a circle. A newtype has to be made from a
+    // single value, so it can be worked with like one.
+    pub fn resolve(&self) -> Result<(), Error> {
+        for (i, (name, kind)) in self.declared.iter().enumerate() {
+            let x = name_of(name);
+            let aliased = |kind: &Kind| matches!(kind, Kind::Alias | Kind::Newtype);
+            // two structs or enums of the same name are left for the semantic pass
+            let taken = self.declared[..i]
+                .iter()
+                .any(|(y, other)| name_of(y) == x && (aliased(kind) || aliased(other)));
+            if taken || aliased(kind) && Type::from_name(&x).is_some() {
+                return Err(Error::RedefinitionError {
+                    name: x,
+                    filepath: name.filepath.clone(),
+                    coord: name.coord,
+                });
+            }
+        }
+        for (name, ty) in self.targets.iter() {
+            self.expect_finite(name, ty, &mut vec![name_of(name)])?;
+        }
+
+        for (name, ty) in self.targets.iter() {
+            if self.kinds[&name_of(name)] == Kind::Newtype && self.fill_in(ty).is_aggregate() {
+                return Err(Error::TypeError {
+                    found: format!("`{}`", ty),
+                    expected: "number, bool, char or str to make a new type from".to_string(),
+                    filepath: name.filepath.clone(),
+                    coord: name.coord,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Follows the aliases and newtypes in `ty` down to the types they stand for. `outer` are
+    // the ones being followed already, so finding one of them again means it never ends.
+    fn expect_finite(&self, name: &Token, ty: &Type, outer: &mut Vec<String>) -> Result<(), Error> {
+        match ty {
+            Type::Array(ty, ..) | Type::Pointer(ty) | Type::Vec(ty) => {
+                self.expect_finite(name, ty, outer)
+            }
+            Type::Alias(x, ..) | Type::Newtype(x, ..) => {
+                if outer.contains(x) {
+                    return Err(Error::TypeError {
+                        found: format!("`{}` standing for itself", x),
+                        expected: "type".to_string(),
+                        filepath: name.filepath.clone(),
+                        coord: name.coord,
+                    });
+                }
+                outer.push(x.clone());
+                self.expect_finite(name, self.target(x), outer)?;
+                outer.pop();
+                Ok(())
+            }
+            Type::Struct(x) if self.kinds.get(x) != Some(&Kind::Struct) => {
+                Err(Error::UndeclaredNameError {
+                    name: x.clone(),
+                    filepath: name.filepath.clone(),
+                    coord: name.coord,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // `ty` with what every alias and newtype in it stands for filled in, all the way down.
+    // `resolve` has to have made sure none of them go round in a circle first.
+    pub fn fill_in(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Array(elem, len) => Type::Array(Box::new(self.fill_in(elem)), *len),
+            Type::Pointer(ty) => Type::Pointer(Box::new(self.fill_in(ty))),
+            Type::Vec(ty) => Type::Vec(Box::new(self.fill_in(ty))),
+            Type::Alias(x, ..) => {
+                Type::Alias(x.clone(), Some(Box::new(self.fill_in(self.target(x)))))
+            }
+            Type::Newtype(x, ..) => {
+                Type::Newtype(x.clone(), Some(Box::new(self.fill_in(self.target(x)))))
+            }
+            ty => ty.clone(),
+        }
+    }
+
+    // what the alias or newtype `name` stands for, as written
+    fn target(&self, name: &str) -> &Type {
+        self.targets
+            .iter()
+            .find(|(y, _)| name_of(y) == name)
+            .map(|(_, ty)| ty)
+            .expect("alias without a type after parsing")
+    }
+}
+
+fn name_of(token: &Token) -> String {
+    match &token.kind {
+        TokenKind::DECL_NAME(x) => x.clone(),
+        _ => panic!("expected a name token"),
+    }
+}